@@ -10,15 +10,23 @@ use sucds::bit_vectors::Rank9Sel;
 use sucds::Serializable;
 use tribles::column::Column;
 use tribles::triblearchive::succinctarchive::{OrderedUniverse, SuccinctArchive, Universe};
-use tribles::{and, types::ShortString, Id, NS};
+use tribles::{and, types::ShortString, Id, Value, NS};
 
 use tribles::test::hashtribleset::HashTribleSet;
 use tribles::{fucid, ufoid};
-use tribles::{find, trible::*};
+use tribles::trible::staging::StagingSet;
+use tribles::{find, par_find, trible::*};
 
 use tribles::patch::{Entry, IdentityOrder};
 use tribles::patch::{SingleSegmentation, PATCH};
+use tribles::query::index::AttributeIndex;
+use tribles::query::stats::SetStatistics;
+use tribles::query::TriblePattern;
+use tribles::tribleset::{TribleIndices, SMALL_SET_LEN};
 use tribles::TribleSet;
+use tribles::BlobSet;
+use tribles::types::{hash::Blake3, ZCString};
+use tribles::Valuelike;
 
 use im::OrdSet;
 
@@ -197,6 +205,34 @@ fn patch_benchmark(c: &mut Criterion) {
         });
     }
 
+    for i in [2, 10, 100, 1000].iter() {
+        group.throughput(Throughput::Elements(total_unioned as u64));
+        group.bench_with_input(BenchmarkId::new("par_union", i), i, |b, &i| {
+            let samples: Vec<Trible> = random_tribles(total_unioned as usize);
+            let patchs: Vec<_> = samples
+                .chunks(total_unioned / i)
+                .map(|samples| {
+                    let mut patch: PATCH<64, IdentityOrder, SingleSegmentation> =
+                        PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+                    for t in samples {
+                        let entry: Entry<64> = Entry::new(&t.data);
+                        patch.insert(&entry);
+                    }
+                    patch
+                })
+                .collect();
+            b.iter_with_large_drop(|| {
+                black_box(&patchs).iter().fold(
+                    PATCH::<64, IdentityOrder, SingleSegmentation>::new(),
+                    |mut a, p| {
+                        a.par_union(p.clone());
+                        a
+                    },
+                )
+            });
+        });
+    }
+
     group.finish();
 }
 
@@ -228,6 +264,54 @@ fn tribleset_benchmark(c: &mut Criterion) {
         });
     }
 
+    for i in [1000000].iter() {
+        group.throughput(Throughput::Elements(*i));
+        group.bench_with_input(BenchmarkId::new("staging", i), i, |b, &i| {
+            let samples = random_tribles(i as usize);
+            b.iter_with_large_drop(|| {
+                let mut staging = StagingSet::new();
+                staging.extend(black_box(&samples).iter().copied());
+                staging.finalize()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Builds and queries sets on both sides of [SMALL_SET_LEN], once in the
+/// small representation and once indexed from the start.
+fn small_set_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tribleset/small");
+
+    for i in [1, SMALL_SET_LEN / 2, SMALL_SET_LEN, 2 * SMALL_SET_LEN] {
+        let samples = random_tribles(i);
+        let sets = [
+            ("small", TribleSet::new()),
+            ("indexed", TribleSet::from(TribleIndices::EMPTY)),
+        ];
+        for (repr, empty) in sets {
+            group.throughput(Throughput::Elements(i as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{}/insert", repr), i), &i, |b, _| {
+                b.iter_with_large_drop(|| {
+                    let mut set = empty.clone();
+                    for t in black_box(&samples) {
+                        set.insert(t);
+                    }
+                    set
+                })
+            });
+
+            let mut set = empty.clone();
+            for t in &samples {
+                set.insert(t);
+            }
+            group.bench_with_input(BenchmarkId::new(format!("{}/query", repr), i), &i, |b, _| {
+                b.iter(|| find!(ctx, (e, a, v), set.pattern::<Value>(e, a, v)).count())
+            });
+        }
+    }
+
     group.finish();
 }
 
@@ -664,6 +748,29 @@ fn query_benchmark(c: &mut Criterion) {
 
     group.sample_size(10);
 
+    group.throughput(Throughput::Elements(kb.len() as u64 / 2));
+    group.bench_function(BenchmarkId::new("tribleset/scan", 1), |b| {
+        b.iter(|| {
+            find!(
+                ctx,
+                (knight, name),
+                knights::pattern!(ctx, kb, [{knight @ name: name}])
+            )
+            .count()
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("tribleset/par_scan", 1), |b| {
+        b.iter(|| {
+            let rows: Vec<Result<(Id, ShortString), _>> = par_find!(
+                ctx,
+                (knight, name),
+                knights::pattern!(ctx, kb, [{knight @ name: name}])
+            );
+            rows.len()
+        })
+    });
+
     let kb_archive: SuccinctArchive<OrderedUniverse, Rank9Sel> = (&kb).into();
 
     group.throughput(Throughput::Elements(1));
@@ -702,6 +809,109 @@ fn query_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+fn attribute_index_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attribute_index");
+    group.sample_size(10);
+
+    let mut kb = TribleSet::new();
+    let mut names = vec![];
+    (0..1000000).for_each(|i| {
+        let knight = ufoid();
+        let name: ShortString = format!("knight {}", i)[..].try_into().unwrap();
+        names.push(name.clone());
+        kb.union(knights::entity!(knight, {
+            name: name,
+            title: "Sir".try_into().unwrap()
+        }));
+    });
+
+    let index = AttributeIndex::build(&kb, knights::ids::name);
+
+    group.throughput(Throughput::Elements(1));
+    group.bench_function(BenchmarkId::new("tribleset/lookup", 1), |b| {
+        b.iter(|| {
+            let name = black_box(names[500000].clone());
+            find!(
+                ctx,
+                (knight),
+                knights::pattern!(ctx, kb, [{knight @ name: (name)}])
+            )
+            .count()
+        })
+    });
+    group.bench_function(BenchmarkId::new("index/lookup", 1), |b| {
+        b.iter(|| {
+            let name = black_box(names[500000].clone());
+            index.lookup(&Valuelike::into_value(&name)).len()
+        })
+    });
+    group.bench_function(BenchmarkId::new("index/find", 1), |b| {
+        b.iter(|| {
+            let name = black_box(names[500000].clone());
+            find!(
+                ctx,
+                (knight, n),
+                and!(index.pattern(knight, n), n.is(name))
+            )
+            .count()
+        })
+    });
+
+    group.finish();
+}
+
+fn statistics_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("statistics");
+    group.sample_size(10);
+
+    // Almost every knight is a "Sir", so the title alone is a bad filter.
+    let mut kb = TribleSet::new();
+    (0..1000000).for_each(|i| {
+        let title = if i % 1000 == 0 { "Dame" } else { "Sir" };
+        kb.union(knights::entity!(ufoid(), {
+            name: Name(EN).fake::<String>()[..].try_into().unwrap(),
+            title: title.try_into().unwrap()
+        }));
+    });
+
+    let kb_archive: SuccinctArchive<OrderedUniverse, Rank9Sel> = (&kb).into();
+    let stats = SetStatistics::analyze(&kb, None);
+    let analyzed = stats.over(&kb_archive);
+
+    for title in ["Dame", "Sir"] {
+        group.bench_function(BenchmarkId::new("archive/skewed", title), |b| {
+            b.iter(|| {
+                find!(
+                    ctx,
+                    (knight, name),
+                    knights::pattern!(ctx, kb_archive, [
+                    {knight @
+                        title: (black_box(title).try_into().unwrap()),
+                        name: name
+                    }])
+                )
+                .count()
+            })
+        });
+        group.bench_function(BenchmarkId::new("analyzed/skewed", title), |b| {
+            b.iter(|| {
+                find!(
+                    ctx,
+                    (knight, name),
+                    knights::pattern!(ctx, analyzed, [
+                    {knight @
+                        title: (black_box(title).try_into().unwrap()),
+                        name: name
+                    }])
+                )
+                .count()
+            })
+        });
+    }
+
+    group.finish();
+}
+
 fn column_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("column");
 
@@ -1066,15 +1276,54 @@ fn oxigraph_benchmark(c: &mut Criterion) {
     group.finish();
 }
 
+fn blobset_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("blobset");
+
+    for i in [1000, 10000, 100000].iter() {
+        group.throughput(Throughput::Elements(*i));
+        group.bench_with_input(BenchmarkId::new("put", i), i, |b, &i| {
+            let samples: Vec<String> = (0..i)
+                .map(|_| Name(EN).fake::<String>().repeat(32))
+                .collect();
+            b.iter_with_large_drop(|| {
+                let mut blobs: BlobSet<Blake3> = BlobSet::new();
+                for name in black_box(&samples) {
+                    blobs.put(ZCString::from(name.clone()));
+                }
+                blobs
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("put_many", i), i, |b, &i| {
+            let samples: Vec<String> = (0..i)
+                .map(|_| Name(EN).fake::<String>().repeat(32))
+                .collect();
+            b.iter_with_large_drop(|| {
+                let mut blobs: BlobSet<Blake3> = BlobSet::new();
+                blobs.put_many(
+                    black_box(&samples)
+                        .iter()
+                        .map(|name| ZCString::from(name.clone())),
+                );
+                blobs
+            });
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     std_benchmark,
     im_benchmark,
     patch_benchmark,
     tribleset_benchmark,
+    small_set_benchmark,
+    blobset_benchmark,
     archive_benchmark,
     entities_benchmark,
     query_benchmark,
+    attribute_index_benchmark,
+    statistics_benchmark,
     column_benchmark,
     hashtribleset_benchmark,
     oxigraph_benchmark
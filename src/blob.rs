@@ -1,4 +1,6 @@
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{self, Debug};
+
 use digest::{consts::U32, Digest};
 
 use crate::{types::Hash, Handle};
@@ -44,3 +46,11 @@ impl BlobParseError {
         }
     }
 }
+
+impl fmt::Display for BlobParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to parse blob: {}", self.msg)
+    }
+}
+
+impl Error for BlobParseError {}
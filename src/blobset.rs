@@ -1,5 +1,6 @@
 use digest::{ Digest, typenum::U32 };
 use anybytes::Bytes;
+use rayon::prelude::*;
 
 use crate::types::Hash;
 use crate::{BlobParseError, Bloblike};
@@ -48,6 +49,30 @@ where
         unsafe { Handle::new(hash) }
     }
 
+    /// Puts all `values` into the set, like repeated calls to [BlobSet::put],
+    /// but hashes the blobs in parallel.
+    ///
+    /// The handles are returned in the order of `values`, identical values
+    /// are stored once and get the same handle.
+    pub fn put_many<T, I>(&mut self, values: I) -> Vec<Handle<H, T>>
+    where
+        T: Bloblike,
+        I: IntoIterator<Item = T>,
+        H: Send,
+    {
+        let blobs: Vec<Bytes> = values.into_iter().map(|value| value.into_blob()).collect();
+        let hashes: Vec<Hash<H>> = blobs.par_iter().map(|blob| Hash::digest(blob)).collect();
+        self.blobs.reserve(blobs.len());
+        hashes
+            .into_iter()
+            .zip(blobs)
+            .map(|(hash, blob)| {
+                self.blobs.entry(hash).or_insert(blob);
+                unsafe { Handle::new(hash) }
+            })
+            .collect()
+    }
+
     pub fn get<'a, T>(&'a self, handle: Handle<H, T>) -> Option<Result<T, BlobParseError>>
     where
         T: Bloblike,
@@ -78,7 +103,8 @@ where
     // allowed to write non-handle typed triples, otherwise they might as well
     // introduce blobs directly.
     pub fn keep(&mut self, tribles: TribleSet) {
-        self.blobs.retain(|k, _| tribles.vae.has_prefix(&k.bytes));
+        let indices = tribles.indices();
+        self.blobs.retain(|k, _| indices.vae.has_prefix(&k.bytes));
     }
 }
 
@@ -160,4 +186,26 @@ mod tests {
         }
         blobs.keep(kb);
     }
+
+    #[test]
+    fn put_many() {
+        let names: Vec<String> = (0..1000).map(|_| Name(EN).fake()).collect();
+        let values = || {
+            names
+                .iter()
+                .chain(names.iter().take(100))
+                .map(|name| ZCString::from(name.clone()))
+        };
+
+        let mut sequential: BlobSet<Blake3> = BlobSet::new();
+        let expected: Vec<Handle<Blake3, ZCString>> = values().map(|v| sequential.put(v)).collect();
+
+        let mut bulk: BlobSet<Blake3> = BlobSet::new();
+        let handles = bulk.put_many(values());
+
+        assert_eq!(handles, expected);
+        assert!(bulk == sequential);
+        assert!(bulk.put_many(Vec::<ZCString>::new()).is_empty());
+        assert!(bulk == sequential);
+    }
 }
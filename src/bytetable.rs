@@ -35,8 +35,9 @@
 //! current bucket, to the corresponding bucket in the upper half.
 //! Incidentally this might flip the hash function used for this entry.
 
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::SeedableRng;
 use std::fmt::Debug;
 use std::sync::Once;
 
@@ -59,7 +60,9 @@ static INIT: Once = Once::new();
 /// used by all tables.
 pub fn init() {
     INIT.call_once(|| {
-        let mut rng = thread_rng();
+        let mut seed = [0; 32];
+        crate::entropy::fill(&mut seed);
+        let mut rng = StdRng::from_seed(seed);
         let mut bytes: [u8; 256] = [0; 256];
 
         for i in 0..256 {
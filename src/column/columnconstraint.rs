@@ -1,6 +1,6 @@
 use crate::{
     id_into_value,
-    query::{Binding, Constraint, Variable, VariableId, VariableSet},
+    query::{Binding, Constraint, ConstraintDescription, Variable, VariableId, VariableSet},
     Value, Valuelike,
 };
 
@@ -114,4 +114,8 @@ where
             _ => panic!(),
         }
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!("column {} {}", self.variable_e, self.variable_v))
+    }
 }
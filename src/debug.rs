@@ -0,0 +1,245 @@
+//! Human readable renderings of tribles for debugging.
+//!
+//! [TribleSet::pretty] groups the tribles of a set by entity and shows
+//! attribute names and decoded values where the
+//! [attribute registry](crate::meta::attributes) knows the attribute,
+//! falling back to hex otherwise. Values are decoded without access to any
+//! blobs, so handles only show the beginning of their hash.
+//!
+//! ```text
+//! 9A2F6B0E1C4D8A7B3E5F2C1D0B9A8E7F {
+//!     title: "Dune"
+//!     author: 7F3E9C1A
+//!     cover: handle:1B2C3D4E
+//! }
+//! ```
+
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::meta::attributes::attribute;
+use crate::trible::{A_END, A_START, E_END, E_START, V_END, V_START};
+use crate::{Id, TribleSet, Value, ID_LEN};
+
+/// The number of entities shown by default before the output is truncated.
+pub const DEFAULT_ENTITY_LIMIT: usize = 32;
+
+/// The number of hex digits shown for ids referenced as values and for
+/// handles.
+const SHORT_HEX_LEN: usize = 8;
+
+/// The number of bytes shown of longer strings before they are cut off.
+pub const STRING_LIMIT: usize = 64;
+
+/// A [fmt::Display] rendering of a [TribleSet], created by
+/// [TribleSet::pretty].
+pub struct Pretty<'a> {
+    set: &'a TribleSet,
+    entity_limit: usize,
+}
+
+impl<'a> Pretty<'a> {
+    /// Shows at most `limit` entities, followed by the number of entities
+    /// left out.
+    pub fn entity_limit(mut self, limit: usize) -> Self {
+        self.entity_limit = limit;
+        self
+    }
+}
+
+impl TribleSet {
+    /// Returns a human readable rendering of the set, see [crate::debug].
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty {
+            set: self,
+            entity_limit: DEFAULT_ENTITY_LIMIT,
+        }
+    }
+}
+
+/// Prints `set` to stderr, rendered with [TribleSet::pretty].
+pub fn pretty_print(set: &TribleSet) {
+    eprintln!("{}", set.pretty());
+}
+
+/// The beginning of `bytes` in hex.
+pub(crate) fn short_hex(bytes: &[u8]) -> String {
+    let mut hex = hex::encode_upper(bytes);
+    hex.truncate(SHORT_HEX_LEN);
+    hex
+}
+
+/// Writes `s` quoted and escaped like [fmt::Debug] does, but cuts strings
+/// longer than `limit` bytes at the preceding character boundary and
+/// appends an ellipsis and the full length, e.g. `"Fear is"… (24 bytes)`.
+pub(crate) fn write_truncated(f: &mut fmt::Formatter<'_>, s: &str, limit: usize) -> fmt::Result {
+    if s.len() <= limit {
+        return fmt::Debug::fmt(s, f);
+    }
+    let mut end = limit;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    fmt::Debug::fmt(&s[..end], f)?;
+    write!(f, "\u{2026} ({} bytes)", s.len())
+}
+
+impl<'a> fmt::Display for Pretty<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut current: Option<Id> = None;
+        let mut entities = 0;
+        for trible in self.set.iter_ordered() {
+            let e: Id = trible[E_START..=E_END].try_into().unwrap();
+            let a: Id = trible[A_START..=A_END].try_into().unwrap();
+            let v: Value = trible[V_START..=V_END].try_into().unwrap();
+
+            if current != Some(e) {
+                if current.is_some() {
+                    writeln!(f, "}}")?;
+                }
+                if entities == self.entity_limit {
+                    current = None;
+                    break;
+                }
+                entities += 1;
+                current = Some(e);
+                writeln!(f, "{} {{", hex::encode_upper(e))?;
+            }
+
+            let registration = attribute(a);
+            let name = registration.map_or_else(|| hex::encode_upper(a), |r| r.name.to_owned());
+            let value = registration
+                .and_then(|r| r.format(v))
+                .unwrap_or_else(|| hex::encode_upper(v));
+            writeln!(f, "    {}: {}", name, value)?;
+        }
+        if current.is_some() {
+            writeln!(f, "}}")?;
+        }
+
+        let total = self.set.indices().eav.iter_prefix::<ID_LEN>().count();
+        if total > entities {
+            writeln!(f, "... {} more entities", total - entities)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use f256::f256;
+    use hex_literal::hex;
+    use hifitime::Epoch;
+
+    use super::*;
+    use crate::types::hash::Blake3;
+    use crate::types::{NsTAIInterval, ShortString, ZCString};
+    use crate::{Handle, Valuelike, NS};
+
+    NS! {
+        pub namespace literature {
+            "8F180883F9FD5F787E9E0AF0DF5866B9" as author: Id;
+            "0DBB530B37B966D137C50D943700EDB2" as firstname: ShortString;
+            "6BAA463FD4EAF45F6A103DB9433E4545" as lastname: ShortString;
+            "A74AA63539354CDA47F387A4C3A8D54C" as title: ShortString;
+            "FCCE870BECA333D059D5CD68C43B98F0" as page_count: f256;
+            "6A03BAF6CFB822F04DA164ADAAEB53F6" as quote: Handle<Blake3, ZCString>;
+            "2E9C5B7A1D4F6E8B0A3C5D7E9F1B2A4C" as published: NsTAIInterval;
+        }
+    }
+
+    const HERBERT: Id = hex!("A1B2C3D4E5F60718293A4B5C6D7E8F90");
+    const DUNE: Id = hex!("0F1E2D3C4B5A69788796A5B4C3D2E1F0");
+
+    fn library() -> TribleSet {
+        let mut set = TribleSet::new();
+        set.union(literature::entity!(HERBERT, {
+            firstname: "Frank".try_into().unwrap(),
+            lastname: "Herbert".try_into().unwrap()
+        }));
+        set.union(literature::entity!(DUNE, {
+            title: "Dune".try_into().unwrap(),
+            author: HERBERT,
+            page_count: f256::from(412),
+            published: (Epoch::from_gregorian_utc(1965, 8, 1, 0, 0, 0, 0),
+                Epoch::from_gregorian_utc(1965, 8, 31, 23, 59, 59, 999_999_999)).into(),
+            quote: Handle::from(&ZCString::from("Fear is the mind-killer.".to_owned()))
+        }));
+        set
+    }
+
+    #[test]
+    fn snapshot() {
+        let quote = Handle::<Blake3, ZCString>::from(&ZCString::from(
+            "Fear is the mind-killer.".to_owned(),
+        ));
+        let expected = format!(
+            "\
+0F1E2D3C4B5A69788796A5B4C3D2E1F0 {{
+    published: 1965-08-01T00:00:00Z/1965-08-31T23:59:59.999999999Z
+    quote: handle:{}
+    author: A1B2C3D4
+    title: \"Dune\"
+    page_count: 412
+}}
+A1B2C3D4E5F60718293A4B5C6D7E8F90 {{
+    firstname: \"Frank\"
+    lastname: \"Herbert\"
+}}
+",
+            short_hex(&quote.hash.bytes)
+        );
+        assert_eq!(library().pretty().to_string(), expected);
+    }
+
+    #[test]
+    fn truncation() {
+        let rendered = library().pretty().entity_limit(1).to_string();
+        assert!(rendered.starts_with("0F1E2D3C4B5A69788796A5B4C3D2E1F0 {"));
+        assert!(rendered.ends_with("}\n... 1 more entities\n"));
+        assert!(!rendered.contains("Herbert"));
+    }
+
+    #[test]
+    fn unknown_attributes() {
+        let mut set = TribleSet::new();
+        let mut trible = [0; 64];
+        trible[A_START..=A_END].copy_from_slice(&[0xEE; 16]);
+        trible[V_START..=V_END].copy_from_slice(&[0xAB; 32]);
+        set.insert_raw(&trible);
+        let rendered = set.pretty().to_string();
+        assert!(rendered.contains(&format!("    {}: {}\n", "EE".repeat(16), "AB".repeat(32))));
+    }
+
+    struct Celsius(i8);
+
+    impl Valuelike for Celsius {
+        fn from_value(value: Value) -> Result<Self, crate::ValueParseError> {
+            Ok(Celsius(value[31] as i8))
+        }
+
+        fn into_value(c: &Self) -> Value {
+            let mut value = [0; 32];
+            value[31] = c.0 as u8;
+            value
+        }
+
+        fn format_value(value: Value) -> Option<String> {
+            Some(format!("{}°C", Celsius::from_value(value).ok()?.0))
+        }
+    }
+
+    NS! {
+        pub namespace weather {
+            "5D1A6C2B8E3F4A7D9C0B1E2F3A4D5C6B" as temperature: Celsius;
+        }
+    }
+
+    #[test]
+    fn declared_formatters() {
+        let set = weather::entity!(DUNE, { temperature: Celsius(-3) });
+        assert!(set.pretty().to_string().contains("    temperature: -3°C\n"));
+    }
+}
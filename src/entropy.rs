@@ -0,0 +1,67 @@
+//! The source of randomness for PATCH hash keys and generated ids.
+//!
+//! With the `entropy` feature, which is enabled by default, random bytes
+//! come from [rand::thread_rng] and thus the operating system. Targets
+//! without an operating system source can build without the feature and
+//! register their own with [set_entropy_source] before the first id or
+//! PATCH is created.
+//!
+//! ```
+//! fn fixed(bytes: &mut [u8]) {
+//!     // Stands in for e.g. `crypto.getRandomValues` in a browser.
+//!     bytes.fill(7);
+//! }
+//!
+//! // Fails when a source was set before.
+//! let _ = tribles::entropy::set_entropy_source(fixed);
+//! ```
+
+use std::fmt;
+use std::sync::OnceLock;
+
+static SOURCE: OnceLock<fn(&mut [u8])> = OnceLock::new();
+
+/// An entropy source was already set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadySet;
+
+impl fmt::Display for AlreadySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the entropy source is already set")
+    }
+}
+
+impl std::error::Error for AlreadySet {}
+
+/// Sets the function that fills buffers with random bytes, instead of the
+/// operating system source.
+///
+/// The source is shared by the whole process and can only be set once.
+/// It is only consulted for randomness that wasn't drawn yet, e.g. a
+/// PATCH hash key that was already chosen stays the same.
+pub fn set_entropy_source(source: fn(&mut [u8])) -> Result<(), AlreadySet> {
+    SOURCE.set(source).map_err(|_| AlreadySet)
+}
+
+/// Fills `bytes` with random bytes from the registered source, or from the
+/// operating system if none was set.
+///
+/// Panics if no source was set and the `entropy` feature is disabled.
+pub fn fill(bytes: &mut [u8]) {
+    if let Some(source) = SOURCE.get() {
+        source(bytes);
+        return;
+    }
+    fill_default(bytes);
+}
+
+#[cfg(feature = "entropy")]
+fn fill_default(bytes: &mut [u8]) {
+    use rand::RngCore;
+    rand::thread_rng().fill_bytes(bytes);
+}
+
+#[cfg(not(feature = "entropy"))]
+fn fill_default(_bytes: &mut [u8]) {
+    panic!("no entropy source, enable the `entropy` feature or call `set_entropy_source`");
+}
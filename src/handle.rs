@@ -70,4 +70,8 @@ impl<H, T> Valuelike for Handle<H, T> {
     fn into_value(value: &Self) -> Value {
         value.hash.bytes
     }
+
+    fn format_value(value: Value) -> Option<String> {
+        Some(format!("handle:{}", crate::debug::short_hex(&value)))
+    }
 }
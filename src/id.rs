@@ -1,4 +1,5 @@
 pub mod fucid;
+pub mod typed;
 pub mod ufoid;
 
 use std::convert::TryInto;
@@ -6,9 +7,6 @@ use std::convert::TryInto;
 pub use fucid::fucid;
 pub use ufoid::ufoid;
 
-use rand::thread_rng;
-use rand::RngCore;
-
 use crate::Value;
 use crate::ValueParseError;
 use crate::Valuelike;
@@ -35,12 +33,15 @@ impl Valuelike for Id {
     fn into_value(id: &Self) -> Value {
         id_into_value(*id)
     }
+
+    fn format_value(value: Value) -> Option<String> {
+        Some(crate::debug::short_hex(&id_from_value(value)))
+    }
 }
 
 pub fn idgen() -> Id {
-    let mut rng = thread_rng();
     let mut id = [0; 16];
-    rng.fill_bytes(&mut id[..]);
+    crate::entropy::fill(&mut id[..]);
 
     id
 }
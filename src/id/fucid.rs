@@ -1,7 +1,5 @@
 use crate::Id;
 
-use rand::thread_rng;
-use rand::RngCore;
 use std::cell::RefCell;
 
 pub struct FUCIDgen {
@@ -14,9 +12,8 @@ impl FUCIDgen {
         Self {
             counter: 0,
             salt: {
-                let mut rng = thread_rng();
                 let mut rand_bytes = [0; 16];
-                rng.fill_bytes(&mut rand_bytes[..]);
+                crate::entropy::fill(&mut rand_bytes[..]);
         
                 u128::from_be_bytes(rand_bytes)
             }
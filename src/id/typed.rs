@@ -0,0 +1,189 @@
+//! Ids tagged with the domain they belong to.
+//!
+//! Every entity is identified by a plain [Id], which makes it easy to pass
+//! the id of a person where the id of a document was expected.
+//! A [TypedId] attaches a zero sized tag type to an [Id], so that attributes
+//! can declare which kind of entity they refer to and the compiler rejects
+//! ids from other domains.
+//!
+//! ```
+//! use tribles::{id::typed::TypedId, typed_id_tag, ufoid, NS};
+//!
+//! typed_id_tag!(PersonTag);
+//! typed_id_tag!(DocumentTag);
+//!
+//! NS! {
+//!     pub namespace library {
+//!         "47BBFB4F34D3EE3E4F6E8E3A4F0A49F7" as author: TypedId<PersonTag>;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let person: TypedId<PersonTag> = TypedId::from_id(ufoid());
+//!     let document: TypedId<DocumentTag> = TypedId::from_id(ufoid());
+//!
+//!     let set = library::entity!(document.id(), { author: person });
+//!     assert_eq!(set.len(), 1);
+//! }
+//! ```
+//!
+//! Passing the id of a document where a person is expected is rejected:
+//!
+//! ```compile_fail
+//! use tribles::{id::typed::TypedId, typed_id_tag, ufoid, NS};
+//!
+//! typed_id_tag!(PersonTag);
+//! typed_id_tag!(DocumentTag);
+//!
+//! NS! {
+//!     pub namespace library {
+//!         "47BBFB4F34D3EE3E4F6E8E3A4F0A49F7" as author: TypedId<PersonTag>;
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let document: TypedId<DocumentTag> = TypedId::from_id(ufoid());
+//!
+//!     library::entity!(document.id(), { author: document });
+//! }
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::{id_from_value, id_into_value, Id, Value, ValueParseError, Valuelike};
+
+/// Declares an uninhabited tag type for use with [TypedId].
+#[macro_export]
+macro_rules! typed_id_tag {
+    ($(#[$Meta:meta])* $Tag:ident) => {
+        $(#[$Meta])*
+        #[derive(Debug)]
+        pub enum $Tag {}
+    };
+}
+
+pub use typed_id_tag;
+
+/// An [Id] that belongs to the domain described by `Tag`.
+#[repr(transparent)]
+pub struct TypedId<Tag> {
+    id: Id,
+    _tag: PhantomData<Tag>,
+}
+
+impl<Tag> TypedId<Tag> {
+    /// Tags a plain id, asserting that it belongs to the domain of `Tag`.
+    pub fn from_id(id: Id) -> Self {
+        TypedId {
+            id,
+            _tag: PhantomData,
+        }
+    }
+
+    /// Drops the tag and returns the plain id.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+impl<Tag> Copy for TypedId<Tag> {}
+
+impl<Tag> Clone for TypedId<Tag> {
+    fn clone(&self) -> TypedId<Tag> {
+        *self
+    }
+}
+
+impl<Tag> PartialEq for TypedId<Tag> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<Tag> Eq for TypedId<Tag> {}
+
+impl<Tag> PartialOrd for TypedId<Tag> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Tag> Ord for TypedId<Tag> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<Tag> Hash for TypedId<Tag> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<Tag> fmt::Debug for TypedId<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TypedId<{}>({:?})", std::any::type_name::<Tag>(), self.id)
+    }
+}
+
+impl<Tag> Valuelike for TypedId<Tag> {
+    fn from_value(value: Value) -> Result<Self, ValueParseError> {
+        Ok(TypedId::from_id(id_from_value(value)))
+    }
+
+    fn into_value(id: &Self) -> Value {
+        id_into_value(id.id)
+    }
+
+    fn format_value(value: Value) -> Option<String> {
+        Id::format_value(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{find, ufoid, NS};
+
+    typed_id_tag!(PersonTag);
+
+    NS! {
+        pub namespace library {
+            "2B8C8EAB41F5FDB8B1F3D8A1F0C6E0D7" as author: TypedId<PersonTag>;
+        }
+    }
+
+    #[test]
+    fn value_roundtrip() {
+        let person: TypedId<PersonTag> = TypedId::from_id(ufoid());
+        let value = Valuelike::into_value(&person);
+        assert_eq!(value, id_into_value(person.id()));
+        assert_eq!(TypedId::<PersonTag>::from_value(value).unwrap(), person);
+    }
+
+    #[test]
+    fn pattern_literal() {
+        let person: TypedId<PersonTag> = TypedId::from_id(ufoid());
+        let book = ufoid();
+        let set = library::entity!(book, { author: person });
+
+        let r: Vec<_> = find!(
+            ctx,
+            (b),
+            library::pattern!(ctx, set, [{b @ author: (person)}])
+        )
+        .collect();
+        assert_eq!(vec![Ok((book,))], r);
+
+        let r: Vec<_> = find!(
+            ctx,
+            (a),
+            library::pattern!(ctx, set, [{(book) @ author: a}])
+        )
+        .collect();
+        assert_eq!(vec![Ok((person,))], r);
+    }
+}
@@ -1,10 +1,8 @@
 use crate::Id;
-use rand::{thread_rng, RngCore};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // Universal Forgettable Ordered IDs
 pub fn ufoid() -> Id {
-    let mut rng = thread_rng();
     let now_in_sys = SystemTime::now();
     let now_since_epoch = now_in_sys
         .duration_since(UNIX_EPOCH)
@@ -13,7 +11,7 @@ pub fn ufoid() -> Id {
 
     let mut id = [0; 16];
     id[0..4].copy_from_slice(&(now_in_ms as u32).to_be_bytes());
-    rng.fill_bytes(&mut id[4..16]);
+    crate::entropy::fill(&mut id[4..16]);
 
     id
 }
@@ -5,12 +5,15 @@ pub mod blob;
 pub mod blobset;
 pub mod bytetable;
 pub mod column;
+pub mod debug;
+pub mod entropy;
 pub mod handle;
 pub mod id;
 pub mod meta;
 pub mod namespace;
 pub mod patch;
 pub mod query;
+#[cfg(feature = "remote")]
 pub mod remote;
 pub mod test;
 pub mod trible;
@@ -28,4 +31,46 @@ pub use tribleset::TribleSet;
 pub use value::*;
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::error::Error;
+
+    fn assert_error<E: Error + Send + Sync + 'static>() {}
+
+    #[test]
+    fn errors_are_send_sync() {
+        assert_error::<crate::ValueParseError>();
+        assert_error::<crate::query::QueryError>();
+        assert_error::<crate::BlobParseError>();
+        assert_error::<crate::types::shortstring::FromStrError>();
+        #[cfg(feature = "signatures")]
+        assert_error::<crate::meta::commit::ValidationError>();
+        #[cfg(feature = "remote")]
+        {
+            use crate::remote::objectstore::{CheckoutErr, CommitErr, ListErr};
+            use crate::remote::repo::{GetError, NotFoundErr, TransferError};
+            assert_error::<ListErr>();
+            assert_error::<CheckoutErr>();
+            assert_error::<CommitErr>();
+            assert_error::<NotFoundErr>();
+            assert_error::<GetError<CheckoutErr>>();
+            assert_error::<TransferError<ListErr, NotFoundErr, CommitErr>>();
+        }
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn nested_sources() {
+        use crate::remote::objectstore::CheckoutErr;
+        use crate::remote::repo::GetError;
+
+        let err: GetError<CheckoutErr> =
+            GetError::Load(CheckoutErr::StoreErr(object_store::Error::NotImplemented));
+        let mut depth = 0;
+        let mut source: Option<&(dyn Error + 'static)> = Some(&err);
+        while let Some(e) = source {
+            depth += 1;
+            source = e.source();
+        }
+        assert_eq!(depth, 3);
+    }
+}
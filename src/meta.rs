@@ -1,3 +1,5 @@
 //! The submodules that can be found here provide functionality to work
 //! with (meta-)data stored in tribles and blobs.
+pub mod attributes;
+#[cfg(feature = "signatures")]
 pub mod commit;
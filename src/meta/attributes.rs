@@ -0,0 +1,197 @@
+//! The attributes compiled into the program.
+//!
+//! Every attribute declared with [crate::NS], in this crate or any other,
+//! registers its id together with its name, value type and the place it
+//! was declared. This allows tools like debug printers to show attribute
+//! names instead of raw ids without being told about the namespaces of
+//! the data they display.
+//!
+//! The same attribute may be declared by several namespaces, as long as
+//! they agree on its name and type. Conflicting declarations of an id are
+//! a bug, so the first lookup panics with both declaration sites.
+//!
+//! ```
+//! use tribles::meta::attributes::{attribute_name, attribute_schema};
+//! use tribles::NS;
+//!
+//! NS! {
+//!     pub namespace books {
+//!         "0C4B3A2F1E0D9C8B7A6F5E4D3C2B1A09" as title: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! assert_eq!(attribute_name(books::ids::title), Some("title"));
+//! assert_eq!(
+//!     attribute_schema(books::ids::title),
+//!     Some(std::any::type_name::<tribles::types::ShortString>())
+//! );
+//! ```
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{Id, Value};
+
+/// An attribute declared by a namespace, created by [crate::NS].
+#[derive(Debug)]
+pub struct AttributeRegistration {
+    pub id: Id,
+    pub name: &'static str,
+    /// The module path of the declaring namespace.
+    pub namespace: &'static str,
+    pub schema: fn() -> &'static str,
+    /// Renders a value of the attribute for debug output, see
+    /// [crate::Valuelike::format_value].
+    pub format: fn(Value) -> Option<String>,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+inventory::collect!(AttributeRegistration);
+
+impl AttributeRegistration {
+    /// The name of the value type of the attribute.
+    pub fn schema(&self) -> &'static str {
+        (self.schema)()
+    }
+
+    /// Renders `value` with the value type of the attribute, or returns
+    /// `None` if the type has no readable rendering.
+    pub fn format(&self, value: Value) -> Option<String> {
+        (self.format)(value)
+    }
+
+    fn site(&self) -> String {
+        format!(
+            "{}::{}: {} at {}:{}",
+            self.namespace,
+            self.name,
+            self.schema(),
+            self.file,
+            self.line
+        )
+    }
+}
+
+#[doc(hidden)]
+pub fn schema_name<T>() -> &'static str {
+    std::any::type_name::<T>()
+}
+
+static REGISTRY: OnceLock<HashMap<Id, &'static AttributeRegistration>> = OnceLock::new();
+
+fn index<I>(registrations: I) -> HashMap<Id, &'static AttributeRegistration>
+where
+    I: IntoIterator<Item = &'static AttributeRegistration>,
+{
+    let mut index = HashMap::new();
+    for registration in registrations {
+        match index.entry(registration.id) {
+            Entry::Vacant(entry) => {
+                entry.insert(registration);
+            }
+            Entry::Occupied(entry) => {
+                let known: &AttributeRegistration = entry.get();
+                if known.name != registration.name || known.schema() != registration.schema() {
+                    panic!(
+                        "attribute id {} is declared twice, as {} and as {}",
+                        hex::encode_upper(registration.id),
+                        known.site(),
+                        registration.site()
+                    );
+                }
+            }
+        }
+    }
+    index
+}
+
+fn registry() -> &'static HashMap<Id, &'static AttributeRegistration> {
+    REGISTRY.get_or_init(|| index(inventory::iter::<AttributeRegistration>))
+}
+
+/// Returns the declaration of the attribute with the given `id`.
+pub fn attribute(id: Id) -> Option<&'static AttributeRegistration> {
+    registry().get(&id).copied()
+}
+
+pub fn attribute_name(id: Id) -> Option<&'static str> {
+    attribute(id).map(|a| a.name)
+}
+
+pub fn attribute_schema(id: Id) -> Option<&'static str> {
+    attribute(id).map(|a| a.schema())
+}
+
+/// Returns all registered attributes.
+pub fn attributes() -> impl Iterator<Item = &'static AttributeRegistration> {
+    registry().values().copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ShortString;
+    use crate::Valuelike;
+    use crate::NS;
+
+    NS! {
+        pub namespace knights {
+            "328edd7583de04e2bedd6bd4fd50e651" as loves: Id;
+            "328147856cc1984f0806dbb824d2b4cb" as name: ShortString;
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let loves = attribute(knights::ids::loves).unwrap();
+        assert_eq!(loves.name, "loves");
+        assert_eq!(loves.schema(), std::any::type_name::<Id>());
+        assert_eq!(
+            attribute_schema(knights::ids::name),
+            Some(std::any::type_name::<ShortString>())
+        );
+        assert_eq!(attribute_name([0; 16]), None);
+        assert!(attributes().any(|a| a.id == knights::ids::name));
+    }
+
+    static TITLE: AttributeRegistration = AttributeRegistration {
+        id: [7; 16],
+        name: "title",
+        namespace: "library",
+        schema: schema_name::<ShortString>,
+        format: <ShortString as Valuelike>::format_value,
+        file: "library.rs",
+        line: 1,
+    };
+    static TITLE_AGAIN: AttributeRegistration = AttributeRegistration {
+        id: [7; 16],
+        name: "title",
+        namespace: "catalog",
+        schema: schema_name::<ShortString>,
+        format: <ShortString as Valuelike>::format_value,
+        file: "catalog.rs",
+        line: 2,
+    };
+    static LABEL: AttributeRegistration = AttributeRegistration {
+        id: [7; 16],
+        name: "label",
+        namespace: "museum",
+        schema: schema_name::<ShortString>,
+        format: <ShortString as Valuelike>::format_value,
+        file: "museum.rs",
+        line: 3,
+    };
+
+    #[test]
+    fn redeclarations() {
+        assert_eq!(index([&TITLE, &TITLE_AGAIN]).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "library::title")]
+    fn collisions() {
+        index([&TITLE, &LABEL]);
+    }
+}
@@ -1,3 +1,6 @@
+use std::error::Error;
+use std::fmt;
+
 use ed25519::Signature;
 use ed25519_dalek::SigningKey;
 use itertools::Itertools;
@@ -28,8 +31,8 @@ NS! {
     }
 }
 
+#[derive(Debug)]
 pub struct ValidationError {
-    #[allow(unused)]
     msg: String,
 }
 
@@ -41,6 +44,14 @@ impl ValidationError {
     }
 }
 
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid commit: {}", self.msg)
+    }
+}
+
+impl Error for ValidationError {}
+
 pub fn sign(
     signing_key: SigningKey,
     handle: Handle<Blake3, SimpleArchive>,
@@ -61,6 +72,34 @@ pub fn sign(
 }
 
 pub fn verify(tribles: TribleSet, commit_id: Id) -> Result<(), ValidationError> {
+    verify_signature(tribles, commit_id).map(|_| ())
+}
+
+/// Like [verify], but additionally requires the commit to be signed by one
+/// of the `trusted` keys.
+///
+/// The verifying key embedded in a commit only proves that the commit
+/// wasn't altered after signing, anybody can re-sign a commit with their
+/// own key. Commits read from an untrusted source should thus be checked
+/// against the keys of their expected authors.
+pub fn verify_trusted(
+    tribles: TribleSet,
+    commit_id: Id,
+    trusted: &[ed::VerifyingKey],
+) -> Result<(), ValidationError> {
+    let key = verify_signature(tribles, commit_id)?;
+    if trusted.contains(&key) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("commit signed by an untrusted key"))
+    }
+}
+
+/// Checks the signature of the commit and returns the key it was made with.
+fn verify_signature(
+    tribles: TribleSet,
+    commit_id: Id,
+) -> Result<ed::VerifyingKey, ValidationError> {
     let (payload, verifying_key, r, s) = find!(
         ctx,
         (payload, key, r, s),
@@ -81,5 +120,32 @@ pub fn verify(tribles: TribleSet, commit_id: Id) -> Result<(), ValidationError>
     let signature = Signature::from_components(r.0, s.0);
     verifying_key
         .verify(&hash, &signature)
-        .map_err(|_| ValidationError::new("couldn't validate signature"))
+        .map_err(|_| ValidationError::new("couldn't validate signature"))?;
+    Ok(verifying_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ufoid, Handle};
+
+    #[test]
+    fn verify_trusted_keys() {
+        let archive = SimpleArchive::from(&TribleSet::new());
+        let handle: Handle<Blake3, SimpleArchive> = Handle::from(&archive);
+        let commit_id = ufoid();
+
+        let author = SigningKey::from_bytes(&[1; 32]);
+        let stranger = SigningKey::from_bytes(&[2; 32]);
+        let trusted = [author.verifying_key()];
+
+        let signed = sign(author, handle, commit_id).unwrap();
+        assert!(verify(signed.clone(), commit_id).is_ok());
+        assert!(verify_trusted(signed, commit_id, &trusted).is_ok());
+
+        // Validly signed, but by a key nobody vouched for.
+        let resigned = sign(stranger, handle, commit_id).unwrap();
+        assert!(verify(resigned.clone(), commit_id).is_ok());
+        assert!(verify_trusted(resigned, commit_id, &trusted).is_err());
+    }
 }
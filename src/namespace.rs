@@ -26,14 +26,36 @@ macro_rules! entity_inner {
             }
         }
     };
-    ($Namespace:path, $Set:expr, $EntityId:expr, {$($FieldName:ident : $Value:expr),* $(,)?}) => {
+    (@fields ($Namespace:path, $set:ident, $id:ident)) => {};
+    (@fields ($Namespace:path, $set:ident, $id:ident) $FieldName:ident ? : $Value:expr $(, $($Rest:tt)*)?) => {
         {
             use $Namespace as ns;
-            $({ let v: ns::types::$FieldName = $Value;
-                $Set.insert(&$crate::trible::Trible::new(
-                $EntityId,
+            let v: Option<ns::types::$FieldName> = $Value;
+            if let Some(v) = v {
+                $set.insert(&$crate::trible::Trible::new(
+                $id,
                 ns::ids::$FieldName,
-                v));})*
+                v));
+            }
+        }
+        entity_inner!(@fields ($Namespace, $set, $id) $($($Rest)*)?);
+    };
+    (@fields ($Namespace:path, $set:ident, $id:ident) $FieldName:ident : $Value:expr $(, $($Rest:tt)*)?) => {
+        {
+            use $Namespace as ns;
+            let v: ns::types::$FieldName = $Value;
+            $set.insert(&$crate::trible::Trible::new(
+            $id,
+            ns::ids::$FieldName,
+            v));
+        }
+        entity_inner!(@fields ($Namespace, $set, $id) $($($Rest)*)?);
+    };
+    ($Namespace:path, $Set:expr, $EntityId:expr, {$($Fields:tt)*}) => {
+        {
+            let set: &mut $crate::TribleSet = $Set;
+            let id = $EntityId;
+            entity_inner!(@fields ($Namespace, set, id) $($Fields)*);
         }
     };
 }
@@ -43,6 +65,13 @@ pub use entity_inner;
 #[doc(hidden)]
 #[macro_export]
 macro_rules! pattern_inner {
+    (@triple ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, $EntityId:ident, $FieldName:ident, _)) => {
+        {
+            use $Namespace as ns;
+            let v_var: $crate::query::Variable<ns::types::$FieldName> = $ctx.next_variable();
+            pattern_inner!(@triple ($constraints, $ctx, $set, $Namespace, $EntityId, $FieldName, v_var));
+        }
+    };
     (@triple ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, $EntityId:ident, $FieldName:ident, ($Value:expr))) => {
         {
             use $crate::query::TriblePattern;
@@ -68,32 +97,93 @@ macro_rules! pattern_inner {
 
     };
 
-    (@entity ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, {($EntityId:expr) @ $($FieldName:ident : $Value:tt),* $(,)?})) => {
+    (@any_triple ($constraints:ident, $ctx:ident, $set:ident, $EntityId:ident, $AttrVar:ident, _)) => {
+        {
+            let v_var: $crate::query::Variable<$crate::Value> = $ctx.next_variable();
+            pattern_inner!(@any_triple ($constraints, $ctx, $set, $EntityId, $AttrVar, v_var));
+        }
+    };
+    (@any_triple ($constraints:ident, $ctx:ident, $set:ident, $EntityId:ident, $AttrVar:ident, ($Value:expr))) => {
+        {
+            use $crate::query::TriblePattern;
+            let v_var = $ctx.next_variable();
+            $constraints.push(Box::new(v_var.is($Value)));
+            $constraints.push(Box::new($set.pattern($EntityId, $AttrVar, v_var)));
+        }
+    };
+    (@any_triple ($constraints:ident, $ctx:ident, $set:ident, $EntityId:ident, $AttrVar:ident, $Value:expr)) => {
+        {
+            use $crate::query::TriblePattern;
+            let v_var = $Value;
+            $constraints.push(Box::new($set.pattern($EntityId, $AttrVar, v_var)));
+        }
+    };
+
+    (@fields ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, $EntityId:ident)) => {};
+    (@fields ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, $EntityId:ident) _ : $Value:tt $(, $($Rest:tt)*)?) => {
+        {
+            let a_var: $crate::query::Variable<$crate::Id> = $ctx.next_variable();
+            pattern_inner!(@any_triple ($constraints, $ctx, $set, $EntityId, a_var, $Value));
+        }
+        pattern_inner!(@fields ($constraints, $ctx, $set, $Namespace, $EntityId) $($($Rest)*)?);
+    };
+    (@fields ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, $EntityId:ident) ? $AttrVar:ident : $Value:tt $(, $($Rest:tt)*)?) => {
+        {
+            let a_var: $crate::query::Variable<$crate::Id> = $AttrVar;
+            pattern_inner!(@any_triple ($constraints, $ctx, $set, $EntityId, a_var, $Value));
+        }
+        pattern_inner!(@fields ($constraints, $ctx, $set, $Namespace, $EntityId) $($($Rest)*)?);
+    };
+    (@fields ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, $EntityId:ident) $FieldName:ident : $Value:tt $(, $($Rest:tt)*)?) => {
+        pattern_inner!(@triple ($constraints, $ctx, $set, $Namespace, $EntityId, $FieldName, $Value));
+        pattern_inner!(@fields ($constraints, $ctx, $set, $Namespace, $EntityId) $($($Rest)*)?);
+    };
+
+    (@entity ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, {($EntityId:expr) @ $($Fields:tt)*})) => {
         {
             let e_var: $crate::query::Variable<$crate::Id> = $ctx.next_variable();
             $constraints.push({ let e: $crate::Id = $EntityId; Box::new(e_var.is(e))});
-            $(pattern_inner!(@triple ($constraints, $ctx, $set, $Namespace, e_var, $FieldName, $Value));)*
+            pattern_inner!(@fields ($constraints, $ctx, $set, $Namespace, e_var) $($Fields)*);
         }
     };
 
-    (@entity ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, {$EntityId:ident @ $($FieldName:ident : $Value:tt),* $(,)?})) => {
+    (@entity ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, {$EntityId:ident @ $($Fields:tt)*})) => {
         {
             let e_var: $crate::query::Variable<$crate::Id> = $EntityId;
-            $(pattern_inner!(@triple ($constraints, $ctx, $set, $Namespace, e_var, $FieldName, $Value));)*
+            pattern_inner!(@fields ($constraints, $ctx, $set, $Namespace, e_var) $($Fields)*);
         }
     };
 
-    (@entity ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, {$($FieldName:ident : $Value:tt),*})) => {
+    (@entity ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path, {$($Fields:tt)*})) => {
         {
             let e_var: $crate::query::Variable<$crate::Id> = $ctx.next_variable();
-            $(pattern_inner!(@triple ($constraints, $ctx, $set, $Namespace, e_var, $FieldName, $Value));)*
+            pattern_inner!(@fields ($constraints, $ctx, $set, $Namespace, e_var) $($Fields)*);
         }
     };
-    ($Namespace:path, $ctx:ident, $set:expr, [$($Entity:tt),*]) => {
+    (@entities ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path)) => {};
+    (@entities ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path) ! $Entity:tt $(, $($Rest:tt)*)?) => {
+        {
+            // Variables created for the negated entity are local to it.
+            let mut shared = $crate::query::VariableSet::new_empty();
+            (0..$ctx.next_index).for_each(|v| shared.set(v));
+            let mut negated: Vec<Box<dyn $crate::query::Constraint>> = vec!();
+            pattern_inner!(@entity (negated, $ctx, $set, $Namespace, $Entity));
+            $constraints.push(Box::new($crate::query::NegationConstraint::new(
+                shared,
+                Box::new($crate::query::IntersectionConstraint::new(negated)),
+            )));
+        }
+        pattern_inner!(@entities ($constraints, $ctx, $set, $Namespace) $($($Rest)*)?);
+    };
+    (@entities ($constraints:ident, $ctx:ident, $set:ident, $Namespace:path) $Entity:tt $(, $($Rest:tt)*)?) => {
+        pattern_inner!(@entity ($constraints, $ctx, $set, $Namespace, $Entity));
+        pattern_inner!(@entities ($constraints, $ctx, $set, $Namespace) $($($Rest)*)?);
+    };
+    ($Namespace:path, $ctx:ident, $set:expr, [$($Entities:tt)*]) => {
         {
             let set = &($set);
             let mut constraints: Vec<Box<dyn $crate::query::Constraint>> = vec!();
-            $(pattern_inner!(@entity (constraints, $ctx, set, $Namespace, $Entity));)*
+            pattern_inner!(@entities (constraints, $ctx, set, $Namespace) $($Entities)*);
             $crate::query::IntersectionConstraint::new(constraints)
         }
     };
@@ -102,15 +192,68 @@ macro_rules! pattern_inner {
 pub use pattern_inner;
 
 pub use hex_literal;
+#[doc(hidden)]
+pub use inventory;
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::Id;
+
+/// The id of an attribute together with the type of its values, as
+/// declared by a namespace in its `attributes` module.
+///
+/// Reading an attribute through its typed id, e.g. with
+/// [EntityView::get](crate::tribleset::EntityView::get), always decodes
+/// the values with the declared type.
+pub struct Attribute<V> {
+    id: Id,
+    _type: PhantomData<fn() -> V>,
+}
+
+impl<V> Attribute<V> {
+    pub const fn new(id: Id) -> Self {
+        Attribute {
+            id,
+            _type: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+}
+
+impl<V> Clone for Attribute<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for Attribute<V> {}
+
+impl<V> fmt::Debug for Attribute<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Attribute")
+            .field(&hex::encode(self.id))
+            .finish()
+    }
+}
 
 /// Define a rust module to represent a namespace.
 /// The module additionally defines `entity!` and `pattern!` macros.
 ///
 /// The `entity!` macro can be used to conveniently create triblesets
-/// containing an entity conforming to the namespace.
+/// containing an entity conforming to the namespace. An attribute written
+/// as `attr?: value` takes an `Option` and is only added if it is `Some`.
 ///
 /// The `pattern!` macro can be used to query datastructures implementing
-/// the [crate::query::TriblePattern] trait.
+/// the [crate::query::TriblePattern] trait. In place of an attribute name
+/// a pattern can use `_` to match any attribute, or `?attr` to also bind
+/// the matched attribute id to the variable `attr`, and `_` in place of a
+/// value matches any value. An entity prefixed with `!` excludes the
+/// results for which it matches; the variables it introduces are local to
+/// it.
 ///
 /// A namespace defined like this
 /// ```
@@ -140,11 +283,21 @@ pub use hex_literal;
 ///       pub use tribles::Id as attr_name;
 ///       pub use tribles::types::ShortString as attr_name2;
 ///   }
+///   pub mod attributes {
+///       use super::*;
+///       use tribles::namespace::Attribute;
+///       pub const attr_name: Attribute<tribles::Id> = Attribute::new(ids::attr_name);
+///       pub const attr_name2: Attribute<tribles::types::ShortString> = Attribute::new(ids::attr_name2);
+///   }
 /// }
 /// ```
 ///
 /// this allows you to access attribute ids and types via their human readable names, e.g.
-/// `namespace_name::ids::attrName` and `namespace_name::types::attrName`.
+/// `namespace_name::ids::attrName` and `namespace_name::types::attrName`,
+/// and `namespace_name::attributes::attrName` combines both.
+///
+/// The attributes are also registered with [crate::meta::attributes], so
+/// their names and types can be looked up by id at runtime.
 #[macro_export]
 macro_rules! NS {
     ($visibility:vis namespace $mod_name:ident {$($FieldId:literal as $FieldName:ident: $FieldType:ty;)*}) => {
@@ -161,6 +314,24 @@ macro_rules! NS {
                 use super::*;
                 $(pub type $FieldName = $FieldType;)*
             }
+            pub mod attributes {
+                #![allow(non_upper_case_globals, unused)]
+                use super::*;
+                $(pub const $FieldName: $crate::namespace::Attribute<$FieldType> =
+                    $crate::namespace::Attribute::new(ids::$FieldName);)*
+            }
+
+            $($crate::namespace::inventory::submit! {
+                $crate::meta::attributes::AttributeRegistration {
+                    id: $crate::namespace::hex_literal::hex!($FieldId),
+                    name: stringify!($FieldName),
+                    namespace: module_path!(),
+                    schema: $crate::meta::attributes::schema_name::<$FieldType>,
+                    format: <$FieldType as $crate::Valuelike>::format_value,
+                    file: file!(),
+                    line: line!(),
+                }
+            })*
 
             #[allow(unused)]
             macro_rules! entity {
@@ -217,7 +388,11 @@ pub use NS;
 mod tests {
     use fake::{faker::name::raw::Name, locales::EN, Fake};
 
-    use crate::{query::find, types::ShortString, ufoid, Id, TribleSet};
+    use crate::{
+        query::{find, QueryError},
+        types::ShortString,
+        ufoid, Id, TribleSet, Value,
+    };
 
     use std::convert::TryInto;
 
@@ -274,6 +449,33 @@ mod tests {
         println!("{:?}", tribles);
     }
 
+    #[test]
+    fn ns_entity_optional() {
+        let juliet = ufoid();
+        let romeo = ufoid();
+        let title: Option<ShortString> = None;
+
+        let tribles = knights::entity!(juliet, {
+            name: "Juliet".try_into().unwrap(),
+            loves?: Some(romeo),
+            title?: title,
+        });
+        assert_eq!(tribles.len(), 2);
+
+        let tribles = knights::entity!(romeo, {
+            name?: None,
+            title?: Some("Prince".try_into().unwrap()),
+            loves: juliet
+        });
+        assert_eq!(tribles.len(), 2);
+
+        let tribles = knights::entity!({
+            name: "Angelica".try_into().unwrap(),
+            title?: None
+        });
+        assert_eq!(tribles.len(), 1);
+    }
+
     #[test]
     fn ns_pattern() {
         let juliet = ufoid();
@@ -311,6 +513,118 @@ mod tests {
         assert_eq!(vec![Ok((juliet, "Juliet".try_into().unwrap(),))], r);
     }
 
+    #[test]
+    fn ns_pattern_wildcard() {
+        let juliet = ufoid();
+        let romeo = ufoid();
+
+        let mut kb = TribleSet::new();
+        kb.union(knights::entity!(juliet, {
+            name: "Juliet".try_into().unwrap(),
+            loves: romeo,
+            title: "Maiden".try_into().unwrap()
+        }));
+        kb.union(knights::entity!(romeo, {
+            name: "Romeo".try_into().unwrap(),
+            loves: juliet
+        }));
+
+        let mut attributes: Vec<Id> = find!(
+            ctx,
+            (attr, value),
+            knights::pattern!(ctx, kb, [{(juliet) @ ?attr: value}])
+        )
+        .map(|r: Result<(Id, Value), QueryError>| r.unwrap().0)
+        .collect();
+        attributes.sort();
+        let mut expected = vec![
+            knights::ids::loves,
+            knights::ids::name,
+            knights::ids::title,
+        ];
+        expected.sort();
+        assert_eq!(attributes, expected);
+
+        let r: Vec<Result<(Id, Value), QueryError>> = find!(
+            ctx,
+            (person, value),
+            knights::pattern!(ctx, kb, [{person @
+                title: ("Maiden".try_into().unwrap()),
+                _: value
+            }])
+        )
+        .collect();
+        assert_eq!(r.len(), 3);
+        assert!(r.iter().all(|r| r.as_ref().unwrap().0 == juliet));
+
+        let r: Vec<Result<(Value, Value), QueryError>> = find!(
+            ctx,
+            (a, b),
+            knights::pattern!(ctx, kb, [{(romeo) @ _: a, _: b}])
+        )
+        .collect();
+        assert_eq!(r.len(), 4);
+    }
+
+    #[test]
+    fn ns_pattern_negation() {
+        let juliet = ufoid();
+        let romeo = ufoid();
+        let angelica = ufoid();
+
+        let mut kb = TribleSet::new();
+        kb.union(knights::entity!(juliet, {
+            name: "Juliet".try_into().unwrap(),
+            loves: romeo,
+            title: "Maiden".try_into().unwrap()
+        }));
+        kb.union(knights::entity!(romeo, {
+            name: "Romeo".try_into().unwrap(),
+            loves: juliet
+        }));
+        kb.union(knights::entity!(angelica, {
+            name: "Angelica".try_into().unwrap(),
+            loves: romeo
+        }));
+
+        let mut untitled: Vec<Id> = find!(
+            ctx,
+            (person, name),
+            knights::pattern!(ctx, kb, [
+                {person @ name: name},
+                !{person @ title: _}
+            ])
+        )
+        .map(|r: Result<(Id, ShortString), QueryError>| r.unwrap().0)
+        .collect();
+        untitled.sort();
+        let mut expected = vec![romeo, angelica];
+        expected.sort();
+        assert_eq!(untitled, expected);
+
+        let unrequited: Vec<_> = find!(
+            ctx,
+            (lover, beloved),
+            knights::pattern!(ctx, kb, [
+                {lover @ loves: beloved},
+                !{beloved @ loves: lover}
+            ])
+        )
+        .collect();
+        assert_eq!(unrequited, vec![Ok((angelica, romeo))]);
+
+        let r: Vec<_> = find!(
+            ctx,
+            (person, name),
+            knights::pattern!(ctx, kb, [
+                {person @ name: name},
+                !{person @ _: _}
+            ])
+        )
+        .collect::<Vec<Result<(Id, ShortString), QueryError>>>();
+        assert!(r.is_empty());
+    }
+
     #[test]
     fn ns_pattern_large() {
         let mut kb = TribleSet::new();
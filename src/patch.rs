@@ -10,35 +10,74 @@ use sptr::Strict;
 use branch::*;
 pub use entry::Entry;
 use leaf::*;
+pub(crate) use leaf::key_hash;
 
 use crate::bytetable;
+use crate::entropy;
 use crate::bytetable::*;
 use core::hash::Hasher;
-use rand::thread_rng;
-use rand::RngCore;
+use rayon::prelude::*;
 use std::cmp::Reverse;
 use std::convert::TryInto;
 use std::fmt;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::mem::transmute;
-use std::sync::Once;
+use std::ops::{Bound, RangeBounds};
+use std::sync::OnceLock;
 
 #[cfg(not(target_pointer_width = "64"))]
 compile_error!("compilation is only possible for 64-bit targets");
 
-static mut SIP_KEY: [u8; 16] = [0; 16];
-static INIT: Once = Once::new();
+static SIP_KEY: OnceLock<[u8; 16]> = OnceLock::new();
+
+/// Subtrees with fewer keys than this are merged sequentially by
+/// [PATCH::par_union].
+pub const PAR_UNION_THRESHOLD: u64 = 1 << 14;
 
 pub fn init() {
-    INIT.call_once(|| {
-        bytetable::init();
+    bytetable::init();
+    sip_key();
+}
 
-        let mut rng = thread_rng();
-        unsafe {
-            rng.fill_bytes(&mut SIP_KEY[..]);
-        }
-    });
+/// The key used for hashing PATCH leaves. It is drawn from
+/// [crate::entropy] the first time it is needed, unless it was set with
+/// [set_hash_key] before.
+pub(crate) fn sip_key() -> &'static [u8; 16] {
+    SIP_KEY.get_or_init(|| {
+        let mut key = [0; 16];
+        entropy::fill(&mut key[..]);
+        key
+    })
+}
+
+/// Whether the hash key was chosen yet, for tests that check that an
+/// operation doesn't need it.
+#[cfg(test)]
+pub(crate) fn hash_key_initialized() -> bool {
+    SIP_KEY.get().is_some()
+}
+
+/// The hash key was already chosen, either by an earlier call to
+/// [set_hash_key] or because a PATCH was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadyInitialized;
+
+impl fmt::Display for AlreadyInitialized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the PATCH hash key is already initialized")
+    }
+}
+
+impl std::error::Error for AlreadyInitialized {}
+
+/// Sets the key used for hashing PATCH leaves instead of a random one,
+/// e.g. to get reproducible hashes.
+///
+/// The key is shared by all PATCHes in the process, so this has to be
+/// called before the first PATCH is created and fails otherwise.
+pub fn set_hash_key(key: [u8; 16]) -> Result<(), AlreadyInitialized> {
+    SIP_KEY.set(key).map_err(|_| AlreadyInitialized)
 }
 
 pub trait KeyOrdering<const KEY_LEN: usize>: Copy + Clone + Debug {
@@ -463,10 +502,86 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
         }
     }
 
+    /// Builds the subtree holding the leaves of `entries` bottom-up.
+    ///
+    /// The entries are paired with their keys in tree order, have to be
+    /// sorted by them and agree on the first `at_depth` bytes. Duplicate
+    /// keys are skipped.
+    pub(crate) fn from_sorted(
+        entries: &[([u8; KEY_LEN], &Entry<KEY_LEN>)],
+        at_depth: usize,
+    ) -> Self {
+        if entries.len() == 1 {
+            return entries[0].1.leaf().with_start(at_depth);
+        }
+        let first = &entries[0].0;
+        let last = &entries[entries.len() - 1].0;
+        let end_depth = match (at_depth..KEY_LEN).find(|&depth| first[depth] != last[depth]) {
+            Some(depth) => depth,
+            // All keys are duplicates of the first one.
+            None => return entries[0].1.leaf().with_start(at_depth),
+        };
+
+        let children: Vec<_> = entries
+            .chunk_by(|(a, _), (b, _)| a[end_depth] == b[end_depth])
+            .map(|run| Self::from_sorted(run, end_depth))
+            .collect();
+        unsafe {
+            let key = first[at_depth];
+            let mut head = match children.len() {
+                2 => Head::new(
+                    HeadTag::Branch2,
+                    key,
+                    Branch2::with_children(end_depth, &children),
+                ),
+                3..=4 => Head::new(
+                    HeadTag::Branch4,
+                    key,
+                    Branch4::with_children(end_depth, &children),
+                ),
+                5..=8 => Head::new(
+                    HeadTag::Branch8,
+                    key,
+                    Branch8::with_children(end_depth, &children),
+                ),
+                9..=16 => Head::new(
+                    HeadTag::Branch16,
+                    key,
+                    Branch16::with_children(end_depth, &children),
+                ),
+                17..=32 => Head::new(
+                    HeadTag::Branch32,
+                    key,
+                    Branch32::with_children(end_depth, &children),
+                ),
+                33..=64 => Head::new(
+                    HeadTag::Branch64,
+                    key,
+                    Branch64::with_children(end_depth, &children),
+                ),
+                65..=128 => Head::new(
+                    HeadTag::Branch128,
+                    key,
+                    Branch128::with_children(end_depth, &children),
+                ),
+                _ => Head::new(
+                    HeadTag::Branch256,
+                    key,
+                    Branch256::with_children(end_depth, &children),
+                ),
+            };
+            for child in children {
+                head.insert_child(child);
+            }
+            head
+        }
+    }
+
     pub(crate) fn infixes<const PREFIX_LEN: usize, const INFIX_LEN: usize, F>(
         &self,
         prefix: &[u8; PREFIX_LEN],
         at_depth: usize,
+        ordered: bool,
         f: &mut F,
     ) where
         F: FnMut([u8; INFIX_LEN]),
@@ -478,7 +593,7 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
                 ),
                 Body::Branch(branch) => {
                     Branch::<KEY_LEN, O, S, [Option<Head<KEY_LEN, O, S>>]>::infixes(
-                        branch, prefix, at_depth, f,
+                        branch, prefix, at_depth, ordered, f,
                     )
                 }
             }
@@ -504,6 +619,25 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
         }
     }
 
+    pub(crate) fn count_prefix<const PREFIX_LEN: usize>(
+        &self,
+        at_depth: usize,
+        prefix: &[u8; PREFIX_LEN],
+    ) -> u64 {
+        unsafe {
+            match self.body() {
+                Body::Leaf(leaf) => {
+                    Leaf::<KEY_LEN>::has_prefix::<O, PREFIX_LEN>(leaf, at_depth, prefix) as u64
+                }
+                Body::Branch(branch) => {
+                    Branch::<KEY_LEN, O, S, [Option<Head<KEY_LEN, O, S>>]>::count_prefix(
+                        branch, at_depth, prefix,
+                    )
+                }
+            }
+        }
+    }
+
     pub(crate) fn segmented_len<const PREFIX_LEN: usize>(
         &self,
         at_depth: usize,
@@ -578,6 +712,130 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
         }
     }
 
+    /// Returns the subtree without the leaves for which `f` returns false,
+    /// or `None` if no leaf is left.
+    ///
+    /// Subtrees without removed leaves are shared, only the branches above
+    /// removed leaves are rebuilt. A branch with a single remaining child is
+    /// replaced by that child.
+    pub(crate) fn retain<F>(&self, f: &mut F) -> Option<Self>
+    where
+        F: FnMut(&[u8; KEY_LEN]) -> bool,
+    {
+        unsafe {
+            match self.body() {
+                Body::Leaf(leaf) => {
+                    if f(&(*leaf).key) {
+                        Some(self.clone())
+                    } else {
+                        None
+                    }
+                }
+                Body::Branch(branch) => {
+                    let end_depth = (*branch).end_depth as usize;
+                    let mut unchanged = true;
+                    let mut children = Vec::new();
+                    for child in self.iter_children().flatten() {
+                        if let Some(retained) = child.retain(f) {
+                            unchanged &= retained.hash() == child.hash();
+                            children.push(retained);
+                        } else {
+                            unchanged = false;
+                        }
+                    }
+                    if unchanged {
+                        return Some(self.clone());
+                    }
+
+                    let mut children = children.into_iter();
+                    let mut first = children.next()?;
+                    let second = match children.next() {
+                        Some(second) => second,
+                        None => {
+                            first.set_key(self.key());
+                            return Some(first);
+                        }
+                    };
+                    let mut head = Branch::<KEY_LEN, O, S, [Option<Head<KEY_LEN, O, S>>; 2]>::new(
+                        self.key(),
+                        end_depth,
+                        first,
+                    );
+                    for child in std::iter::once(second).chain(children) {
+                        head.upsert(child, |_, _| unreachable!());
+                    }
+                    Some(head)
+                }
+            }
+        }
+    }
+
+    /// Like [Head::union], but merges the children that both branches have
+    /// in common on multiple threads, if they start at the same depth and
+    /// contain at least `threshold` leaves together.
+    pub(crate) fn par_union(&mut self, other: Self, at_depth: usize, threshold: u64) {
+        let depth = self.end_depth();
+        if self.tag() == HeadTag::Leaf
+            || other.tag() == HeadTag::Leaf
+            || self.count() + other.count() < threshold
+            || depth != other.end_depth()
+            || self.hash() == other.hash()
+        {
+            return self.union(other, at_depth);
+        }
+        let self_key = self.leaf_key();
+        let other_key = other.leaf_key();
+        if (at_depth..depth).any(|d| self_key[O::key_index(d)] != other_key[O::key_index(d)]) {
+            return self.union(other, at_depth);
+        }
+
+        let mut others: Vec<Option<Self>> = (0..256).map(|_| None).collect();
+        other.take_or_clone_children(|child| {
+            let key = child.key() as usize;
+            others[key] = Some(child);
+        });
+
+        self.cow();
+        unsafe {
+            let branch = match self.body() {
+                Body::Branch(branch) => branch,
+                Body::Leaf(_) => unreachable!(),
+            };
+            let mut pairs = Vec::new();
+            for child in (*branch).child_table.iter_mut().flatten() {
+                if let Some(inserted) = others[child.key() as usize].take() {
+                    pairs.push((child, inserted));
+                }
+            }
+            let changes: Vec<_> = pairs
+                .into_par_iter()
+                .map(|(child, inserted)| {
+                    let old_hash = child.hash();
+                    let old_segment_count = child.count_segment(depth);
+                    let old_leaf_count = child.count();
+                    child.par_union(inserted, depth, threshold);
+                    (
+                        old_hash ^ child.hash(),
+                        old_segment_count,
+                        child.count_segment(depth),
+                        old_leaf_count,
+                        child.count(),
+                    )
+                })
+                .collect();
+            for (hash, old_segment_count, segment_count, old_leaf_count, leaf_count) in changes {
+                (*branch).hash ^= hash;
+                (*branch).segment_count =
+                    ((*branch).segment_count - old_segment_count) + segment_count;
+                (*branch).leaf_count = ((*branch).leaf_count - old_leaf_count) + leaf_count;
+            }
+
+            for inserted in others.into_iter().flatten() {
+                self.upsert(inserted, |_, _| unreachable!());
+            }
+        }
+    }
+
     pub(crate) fn take_or_clone_children<F>(&self, f: F)
     where
         F: FnMut(Self),
@@ -720,11 +978,23 @@ where
     O: KeyOrdering<KEY_LEN>,
     S: KeySegmentation<KEY_LEN>,
 {
-    pub fn new() -> Self {
-        init();
+    /// Creates an empty PATCH.
+    ///
+    /// The hash key and byte tables are only initialized once the first
+    /// [Entry] is created, so empty PATCHes can be constructed in const
+    /// contexts and read from without any setup cost.
+    pub const fn new() -> Self {
         PATCH { root: None }
     }
 
+    /// Creates a PATCH that only contains `entry`, with the entry's leaf as
+    /// its root.
+    pub fn singleton(entry: &Entry<KEY_LEN>) -> Self {
+        PATCH {
+            root: Some(entry.leaf()),
+        }
+    }
+
     pub fn insert(&mut self, entry: &Entry<KEY_LEN>) {
         if let Some(root) = &mut self.root {
             root.insert_leaf(entry.leaf(), 0);
@@ -733,6 +1003,31 @@ where
         }
     }
 
+    /// Builds a PATCH from `entries` in a single bottom-up pass.
+    ///
+    /// The entries are sorted by their keys in tree order first, which
+    /// lets the tree be built without descending from the root for every
+    /// key, each branch is only created once all of its children exist.
+    /// Duplicate keys are skipped.
+    pub fn from_entries(entries: &[Entry<KEY_LEN>]) -> Self {
+        if entries.is_empty() {
+            return PATCH::new();
+        }
+        let mut sorted: Vec<_> = entries
+            .iter()
+            .map(|entry| (O::tree_ordered(entry.key()), entry))
+            .collect();
+        sorted.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        PATCH {
+            root: Some(Head::from_sorted(&sorted, 0)),
+        }
+    }
+
+    /// The XOR of the [key_hash]es of all keys, zero for an empty PATCH.
+    pub(crate) fn root_hash(&self) -> u128 {
+        self.root.as_ref().map_or(0, |root| root.hash())
+    }
+
     pub fn len(&self) -> u64 {
         if let Some(root) = &self.root {
             root.count()
@@ -741,17 +1036,46 @@ where
         }
     }
 
+    /// Calls `f` for every distinct infix following `prefix`, in ascending
+    /// tree order.
     pub fn infixes<const PREFIX_LEN: usize, const INFIX_LEN: usize, F>(
         &self,
         prefix: &[u8; PREFIX_LEN],
         mut f: F,
     ) where
         F: FnMut([u8; INFIX_LEN]),
+    {
+        self.walk_infixes(prefix, true, &mut f)
+    }
+
+    /// Like [PATCH::infixes], but visits the infixes in the unspecified
+    /// order of the underlying hash tables, which saves ordering the
+    /// children of every visited branch.
+    pub fn infixes_unordered<const PREFIX_LEN: usize, const INFIX_LEN: usize, F>(
+        &self,
+        prefix: &[u8; PREFIX_LEN],
+        mut f: F,
+    ) where
+        F: FnMut([u8; INFIX_LEN]),
+    {
+        self.walk_infixes(prefix, false, &mut f)
+    }
+
+    fn walk_infixes<const PREFIX_LEN: usize, const INFIX_LEN: usize, F>(
+        &self,
+        prefix: &[u8; PREFIX_LEN],
+        ordered: bool,
+        f: &mut F,
+    ) where
+        F: FnMut([u8; INFIX_LEN]),
     {
         assert!(PREFIX_LEN + INFIX_LEN <= KEY_LEN);
-        assert!(S::segment(PREFIX_LEN) == S::segment(PREFIX_LEN + INFIX_LEN - 1));
+        assert!(
+            S::segment(O::key_index(PREFIX_LEN))
+                == S::segment(O::key_index(PREFIX_LEN + INFIX_LEN - 1))
+        );
         if let Some(root) = &self.root {
-            root.infixes(prefix, 0, &mut f);
+            root.infixes(prefix, 0, ordered, f);
         }
     }
 
@@ -771,12 +1095,42 @@ where
         }
     }
 
+    /// The number of keys starting with `prefix`.
+    pub fn count_prefix<const PREFIX_LEN: usize>(&self, prefix: &[u8; PREFIX_LEN]) -> u64 {
+        if let Some(root) = &self.root {
+            root.count_prefix(0, prefix)
+        } else {
+            0
+        }
+    }
+
+    /// Iterates over the distinct prefixes of length `PREFIX_LEN` in
+    /// ascending tree order, together with the number of keys below them.
     pub fn iter_prefix<'a, const PREFIX_LEN: usize>(
         &'a self,
     ) -> PATCHPrefixIterator<'a, KEY_LEN, PREFIX_LEN, O, S> {
         PATCHPrefixIterator::new(self)
     }
 
+    /// Iterates over the keys within `range` in ascending key order.
+    ///
+    /// Subtrees that lie entirely outside of the range are skipped without
+    /// being visited. This requires the range to be contiguous in the tree,
+    /// which is the case when the key bytes that differ between the bounds
+    /// keep their position in the tree ordering, e.g. for all ranges with
+    /// [IdentityOrder], or for ranges within a single segment that the
+    /// ordering moves to the front. Use [PATCH::infixes] for the other cases.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ordering doesn't keep the range contiguous.
+    pub fn iter_range<'a, R>(&'a self, range: R) -> PATCHRangeIterator<'a, KEY_LEN, O, S>
+    where
+        R: RangeBounds<[u8; KEY_LEN]>,
+    {
+        PATCHRangeIterator::new(self, range)
+    }
+
     pub fn union(&mut self, other: Self) {
         if let Some(other) = other.root {
             if let Some(root) = &mut self.root {
@@ -786,6 +1140,37 @@ where
             }
         }
     }
+
+    /// Like [PATCH::union], but merges large subtrees on multiple threads.
+    pub fn par_union(&mut self, other: Self) {
+        self.par_union_with_threshold(other, PAR_UNION_THRESHOLD)
+    }
+
+    /// Like [PATCH::par_union], but subtrees with fewer than `threshold`
+    /// keys are merged sequentially.
+    pub fn par_union_with_threshold(&mut self, other: Self, threshold: u64) {
+        if let Some(other) = other.root {
+            if let Some(root) = &mut self.root {
+                root.par_union(other, 0, threshold);
+            } else {
+                self.root.replace(other);
+            }
+        }
+    }
+
+    /// Removes all keys for which `f` returns false.
+    ///
+    /// The keys are passed as they were given to [Entry::new]. The tree is
+    /// pruned in a single pass that only copies the branches above removed
+    /// keys, all other subtrees stay shared with clones of this PATCH.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[u8; KEY_LEN]) -> bool,
+    {
+        if let Some(root) = self.root.take() {
+            self.root = root.retain(&mut f);
+        }
+    }
 }
 
 impl<const KEY_LEN: usize, O, S> PartialEq for PATCH<KEY_LEN, O, S>
@@ -805,6 +1190,8 @@ where
 {
 }
 
+/// Iterates over all keys in the unspecified order of the underlying
+/// hash tables, use [PATCH::iter_prefix] for ordered iteration.
 impl<'a, const KEY_LEN: usize, O, S> IntoIterator for &'a PATCH<KEY_LEN, O, S>
 where
     O: KeyOrdering<KEY_LEN>,
@@ -937,9 +1324,109 @@ impl<
     }
 }
 
+fn tree_ordered_bound<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>>(
+    bound: Bound<&[u8; KEY_LEN]>,
+) -> Bound<[u8; KEY_LEN]> {
+    match bound {
+        Bound::Included(key) => Bound::Included(O::tree_ordered(key)),
+        Bound::Excluded(key) => Bound::Excluded(O::tree_ordered(key)),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+pub struct PATCHRangeIterator<
+    'a,
+    const KEY_LEN: usize,
+    O: KeyOrdering<KEY_LEN>,
+    S: KeySegmentation<KEY_LEN>,
+> {
+    // The bounds in tree order.
+    start: Bound<[u8; KEY_LEN]>,
+    end: Bound<[u8; KEY_LEN]>,
+    stack: Vec<&'a Head<KEY_LEN, O, S>>,
+}
+
+impl<'a, const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
+    PATCHRangeIterator<'a, KEY_LEN, O, S>
+{
+    fn new<R: RangeBounds<[u8; KEY_LEN]>>(patch: &'a PATCH<KEY_LEN, O, S>, range: R) -> Self {
+        // All keys in the range share the bytes up to the first one in which
+        // the bounds differ, the bytes after it have to stay in place.
+        let shared = match (range.start_bound(), range.end_bound()) {
+            (
+                Bound::Included(start) | Bound::Excluded(start),
+                Bound::Included(end) | Bound::Excluded(end),
+            ) => start
+                .iter()
+                .zip(end.iter())
+                .take_while(|(s, e)| s == e)
+                .count(),
+            _ => 0,
+        };
+        assert!(
+            (shared..KEY_LEN).all(|i| O::tree_index(i) == i),
+            "the key ordering doesn't keep the range contiguous in the tree"
+        );
+        PATCHRangeIterator {
+            start: tree_ordered_bound::<KEY_LEN, O>(range.start_bound()),
+            end: tree_ordered_bound::<KEY_LEN, O>(range.end_bound()),
+            stack: patch.root.iter().collect(),
+        }
+    }
+
+    /// Whether all keys starting with `prefix` are smaller than the range.
+    fn below(&self, prefix: &[u8]) -> bool {
+        match &self.start {
+            Bound::Unbounded => false,
+            Bound::Included(start) => prefix < &start[..prefix.len()],
+            Bound::Excluded(start) if prefix.len() == KEY_LEN => prefix <= &start[..],
+            Bound::Excluded(start) => prefix < &start[..prefix.len()],
+        }
+    }
+
+    /// Whether all keys starting with `prefix` are larger than the range.
+    fn above(&self, prefix: &[u8]) -> bool {
+        match &self.end {
+            Bound::Unbounded => false,
+            Bound::Included(end) => prefix > &end[..prefix.len()],
+            Bound::Excluded(end) if prefix.len() == KEY_LEN => prefix >= &end[..],
+            Bound::Excluded(end) => prefix > &end[..prefix.len()],
+        }
+    }
+}
+
+impl<'a, const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>> Iterator
+    for PATCHRangeIterator<'a, KEY_LEN, O, S>
+{
+    type Item = [u8; KEY_LEN];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(head) = self.stack.pop() {
+            let key = O::tree_ordered(head.leaf_key());
+            let prefix = &key[0..head.end_depth()];
+            if self.below(prefix) {
+                continue;
+            }
+            if self.above(prefix) {
+                // Everything left on the stack comes after this subtree.
+                self.stack.clear();
+                return None;
+            }
+            if head.tag() == HeadTag::Leaf {
+                return Some(*head.leaf_key());
+            }
+            let mut children: Vec<_> = head.iter_children().filter_map(|c| c.as_ref()).collect();
+            children.sort_by_key(|&k| Reverse(k.key())); // We need to reverse here because we pop from the vec.
+            self.stack.extend(children);
+        }
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trible::{AEVOrder, TribleSegmentation};
     use itertools::Itertools;
     use proptest::prelude::*;
     use std::collections::HashSet;
@@ -947,6 +1434,37 @@ mod tests {
     use std::iter::FromIterator;
     use std::mem;
 
+    #[test]
+    fn hash_key_is_fixed_once_used() {
+        init();
+        let key = *sip_key();
+        let other = [!key[0]; 16];
+        assert_eq!(set_hash_key(other), Err(AlreadyInitialized));
+        assert_eq!(*sip_key(), key);
+    }
+
+    #[test]
+    fn hash_key_race_has_one_winner() {
+        let handles: Vec<_> = (0..8u8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let won = set_hash_key([i; 16]).is_ok();
+                    (i, won, *sip_key())
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let key = *sip_key();
+        assert!(results.iter().all(|&(_, _, seen)| seen == key));
+        let winners: Vec<_> = results.iter().filter(|&&(_, won, _)| won).collect();
+        // The key may already have been chosen by another test.
+        assert!(winners.len() <= 1);
+        for &&(i, _, _) in &winners {
+            assert_eq!(key, [i; 16]);
+        }
+    }
+
     #[test]
     fn head_tag() {
         let head = unsafe {
@@ -1097,6 +1615,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn retain_collapses_branches() {
+        let mut tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        let mut a = [0u8; 64];
+        let mut b = [0u8; 64];
+        let mut c = [0u8; 64];
+        a[63] = 1;
+        b[63] = 2;
+        c[0] = 1;
+        for key in [a, b, c] {
+            tree.insert(&Entry::new(&key));
+        }
+
+        let unchanged = tree.clone();
+        tree.retain(|_| true);
+        assert_eq!(tree, unchanged);
+        assert_eq!(tree.len(), 3);
+
+        // Removing `b` leaves `a` as the only child of the deepest branch.
+        tree.retain(|key| key != &b);
+        assert_eq!(tree.len(), 2);
+        assert!(tree.has_prefix(&a));
+        assert!(!tree.has_prefix(&b));
+        assert!(tree.has_prefix(&c));
+
+        let mut expected = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        expected.insert(&Entry::new(&a));
+        expected.insert(&Entry::new(&c));
+        assert_eq!(tree, expected);
+        assert_eq!(unchanged.len(), 3);
+
+        tree.insert(&Entry::new(&b));
+        assert_eq!(tree, unchanged);
+
+        tree.retain(|_| false);
+        assert_eq!(tree.len(), 0);
+        assert_eq!(tree, PATCH::new());
+    }
+
+    #[test]
+    fn iter_range_bounds() {
+        let mut tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        let keys: Vec<[u8; 64]> = (0..10u8)
+            .map(|i| {
+                let mut key = [0u8; 64];
+                key[7] = i;
+                key[63] = i;
+                key
+            })
+            .collect();
+        for key in &keys {
+            tree.insert(&Entry::new(key));
+        }
+
+        assert_eq!(Vec::from_iter(tree.iter_range(..)), keys);
+        assert_eq!(
+            Vec::from_iter(tree.iter_range(keys[2]..keys[5])),
+            keys[2..5]
+        );
+        assert_eq!(
+            Vec::from_iter(tree.iter_range(keys[2]..=keys[5])),
+            keys[2..=5]
+        );
+        assert_eq!(Vec::from_iter(tree.iter_range(..keys[3])), keys[..3]);
+        assert_eq!(Vec::from_iter(tree.iter_range(keys[8]..)), keys[8..]);
+        assert_eq!(
+            Vec::from_iter(tree.iter_range((Bound::Excluded(keys[2]), Bound::Excluded(keys[5])))),
+            keys[3..5]
+        );
+        assert_eq!(tree.iter_range(keys[5]..keys[5]).count(), 0);
+        assert_eq!(tree.iter_range(keys[6]..keys[2]).count(), 0);
+        let empty = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        assert_eq!(empty.iter_range(..).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn iter_range_rejects_scattered_ranges() {
+        // The keys between these bounds have every attribute, so they are
+        // spread over the whole AEV tree.
+        let tree = PATCH::<64, AEVOrder, TribleSegmentation>::new();
+        tree.iter_range([0; 64]..[1; 64]);
+    }
+
     proptest! {
     #[test]
     fn tree_insert(keys in prop::collection::vec(prop::collection::vec(0u8..255, 64), 1..1024)) {
@@ -1122,6 +1724,23 @@ mod tests {
         prop_assert_eq!(set.len() as u64, tree.len())
     }
 
+    #[test]
+    fn from_entries_equals_insert(keys in prop::collection::vec(prop::collection::vec(0u8..4, 64), 1..1024)) {
+        let mut tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        let mut entries = Vec::new();
+        for key in keys {
+            let key: [u8; 64] = key.try_into().unwrap();
+            let entry = Entry::new(&key);
+            tree.insert(&entry);
+            entries.push(entry);
+        }
+        let built = PATCH::<64, IdentityOrder, SingleSegmentation>::from_entries(&entries);
+
+        prop_assert_eq!(built.len(), tree.len());
+        prop_assert_eq!(Vec::from_iter(&built), Vec::from_iter(&tree));
+        prop_assert_eq!(built, tree);
+    }
+
     #[test]
     fn tree_infixes(keys in prop::collection::vec(prop::collection::vec(0u8..255, 64), 1..1024)) {
         let mut tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
@@ -1198,6 +1817,128 @@ mod tests {
 
         prop_assert_eq!(set_vec, tree_vec);
         }
+    #[test]
+    fn tree_retain(keys in prop::collection::vec(prop::collection::vec(0u8..=255, 64), 1..1024),
+                   modulus in 1u8..5) {
+        let mut tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        let mut set = HashSet::new();
+        for key in keys {
+            let key: [u8; 64] = key.try_into().unwrap();
+            tree.insert(&Entry::new(&key));
+            set.insert(key);
+        }
+        let original = tree.clone();
+
+        tree.retain(|key| key[63] % modulus == 0);
+        set.retain(|key| key[63] % modulus == 0);
+
+        let mut expected = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        for key in &set {
+            expected.insert(&Entry::new(key));
+        }
+        let mut set_vec = Vec::from_iter(set.into_iter());
+        let mut tree_vec = Vec::from_iter(&tree);
+        set_vec.sort();
+        tree_vec.sort();
+
+        prop_assert_eq!(&set_vec, &tree_vec);
+        prop_assert_eq!(set_vec.len() as u64, tree.len());
+        prop_assert_eq!(tree.segmented_len(&[0; 0]), tree.len());
+        prop_assert!(tree == expected);
+        prop_assert!(original.len() >= tree.len());
+    }
+
+    #[test]
+    fn tree_iter_range(keys in prop::collection::vec(prop::collection::vec(0u8..=3, 64), 1..1024),
+                       a in prop::collection::vec(0u8..=3, 64),
+                       b in prop::collection::vec(0u8..=3, 64)) {
+        let mut tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        let mut set = HashSet::new();
+        for key in keys {
+            let key: [u8; 64] = key.try_into().unwrap();
+            tree.insert(&Entry::new(&key));
+            set.insert(key);
+        }
+        let mut sorted = Vec::from_iter(set.into_iter());
+        sorted.sort();
+        let start: [u8; 64] = a.try_into().unwrap();
+        let end: [u8; 64] = b.try_into().unwrap();
+
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| (start..end).contains(k)).collect();
+        prop_assert_eq!(Vec::from_iter(tree.iter_range(start..end)), expected);
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| (start..=end).contains(k)).collect();
+        prop_assert_eq!(Vec::from_iter(tree.iter_range(start..=end)), expected);
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| (..end).contains(k)).collect();
+        prop_assert_eq!(Vec::from_iter(tree.iter_range(..end)), expected);
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| k > &start).collect();
+        prop_assert_eq!(
+            Vec::from_iter(tree.iter_range((Bound::Excluded(start), Bound::Unbounded))),
+            expected
+        );
+    }
+
+    #[test]
+    fn tree_iter_range_reordered(keys in prop::collection::vec((0u8..=1, prop::collection::vec(0u8..=3, 32)), 1..1024),
+                                 shared in 0u8..=1,
+                                 a in prop::collection::vec(0u8..=3, 32),
+                                 b in prop::collection::vec(0u8..=3, 32)) {
+        // Ranges over the values of one entity and attribute are contiguous
+        // in the AEV ordering.
+        let mut tree = PATCH::<64, AEVOrder, TribleSegmentation>::new();
+        let mut set = HashSet::new();
+        for (prefix, value) in keys {
+            let mut key = [prefix; 64];
+            key[32..].copy_from_slice(&value);
+            tree.insert(&Entry::new(&key));
+            set.insert(key);
+        }
+        let mut sorted = Vec::from_iter(set.into_iter());
+        sorted.sort();
+        let mut start = [shared; 64];
+        start[32..].copy_from_slice(&a);
+        let mut end = [shared; 64];
+        end[32..].copy_from_slice(&b);
+
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| (start..=end).contains(k)).collect();
+        prop_assert_eq!(Vec::from_iter(tree.iter_range(start..=end)), expected);
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| (start..end).contains(k)).collect();
+        prop_assert_eq!(Vec::from_iter(tree.iter_range(start..end)), expected);
+        let expected: Vec<_> = sorted.iter().copied().filter(|k| k > &start && k <= &end).collect();
+        prop_assert_eq!(
+            Vec::from_iter(tree.iter_range((Bound::Excluded(start), Bound::Included(end)))),
+            expected
+        );
+    }
+
+    #[test]
+    fn tree_par_union(left in prop::collection::vec(prop::collection::vec(0u8..=3, 64), 1..1024),
+                      right in prop::collection::vec(prop::collection::vec(0u8..=3, 64), 1..1024),
+                      threshold in 0u64..64) {
+        let mut left_tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        for key in left {
+            let key: [u8; 64] = key.try_into().unwrap();
+            left_tree.insert(&Entry::new(&key));
+        }
+        let mut right_tree = PATCH::<64, IdentityOrder, SingleSegmentation>::new();
+        for key in right {
+            let key: [u8; 64] = key.try_into().unwrap();
+            right_tree.insert(&Entry::new(&key));
+        }
+
+        let mut sequential = left_tree.clone();
+        sequential.union(right_tree.clone());
+        let mut parallel = left_tree.clone();
+        parallel.par_union_with_threshold(right_tree, threshold);
+
+        prop_assert!(parallel == sequential);
+        prop_assert_eq!(parallel.len(), sequential.len());
+        prop_assert_eq!(parallel.segmented_len(&[0; 0]), sequential.segmented_len(&[0; 0]));
+        prop_assert_eq!(
+            Vec::from_iter(parallel.iter_prefix::<64>()),
+            Vec::from_iter(sequential.iter_prefix::<64>())
+        );
+    }
+
         #[test]
     fn tree_union_empty(left in prop::collection::vec(prop::collection::vec(0u8..=255, 64), 2)) {
         let mut set = HashSet::new();
@@ -1,4 +1,5 @@
 use super::*;
+use crate::bitset::ByteBitset;
 use core::sync::atomic;
 use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 use std::alloc::{alloc, dealloc, Layout};
@@ -81,6 +82,7 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
         branch: *mut Self,
         prefix: &[u8; PREFIX_LEN],
         at_depth: usize,
+        ordered: bool,
         f: &mut F,
     ) where
         F: FnMut([u8; INFIX_LEN]),
@@ -109,15 +111,27 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
         // The prefix ends in a child of this node.
         if PREFIX_LEN > node_end_depth {
             if let Some(child) = (*branch).child_table.table_get(prefix[node_end_depth]) {
-                child.infixes(prefix, node_end_depth, f);
+                child.infixes(prefix, node_end_depth, ordered, f);
             }
             return;
         }
 
         // The prefix ends in this node, but the infix ends in a child.
-        for entry in &(*branch).child_table {
-            if let Some(entry) = entry {
-                entry.infixes(prefix, node_end_depth, f);
+        if ordered {
+            let mut keys = ByteBitset::new_empty();
+            for entry in (*branch).child_table.iter().flatten() {
+                keys.set(entry.key());
+            }
+            while let Some(key) = keys.drain_next_ascending() {
+                if let Some(entry) = (*branch).child_table.table_get(key) {
+                    entry.infixes(prefix, node_end_depth, ordered, f);
+                }
+            }
+        } else {
+            for entry in &(*branch).child_table {
+                if let Some(entry) = entry {
+                    entry.infixes(prefix, node_end_depth, ordered, f);
+                }
             }
         }
     }
@@ -148,6 +162,27 @@ impl<const KEY_LEN: usize, O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>
         return false;
     }
 
+    pub(super) unsafe fn count_prefix<const PREFIX_LEN: usize>(
+        node: *const Self,
+        at_depth: usize,
+        prefix: &[u8; PREFIX_LEN],
+    ) -> u64 {
+        let node_end_depth = (*node).end_depth as usize;
+        let leaf_key: &[u8; KEY_LEN] = &(*(*node).childleaf).key;
+        for depth in at_depth..std::cmp::min(node_end_depth, PREFIX_LEN) {
+            if leaf_key[O::key_index(depth)] != prefix[depth] {
+                return 0;
+            }
+        }
+        if PREFIX_LEN <= node_end_depth {
+            return (*node).leaf_count;
+        }
+        if let Some(child) = (*node).child_table.table_get(prefix[node_end_depth]) {
+            return child.count_prefix(node_end_depth, prefix);
+        }
+        return 0;
+    }
+
     pub(super) unsafe fn segmented_len<const PREFIX_LEN: usize>(
         node: *const Self,
         at_depth: usize,
@@ -216,6 +251,36 @@ impl<
         S: KeySegmentation<KEY_LEN>,
     > Branch<KEY_LEN, O, S, [Option<Head<KEY_LEN, O, S>>; SLOT_COUNT]>
 {
+    /// Allocates a branch that accounts for `children`, which still have
+    /// to be inserted into its empty child table.
+    pub(super) fn with_children(end_depth: usize, children: &[Head<KEY_LEN, O, S>]) -> *mut Self {
+        unsafe {
+            let layout = Layout::new::<Self>();
+            let ptr = alloc(layout) as *mut Self;
+            if ptr.is_null() {
+                panic!("Allocation failed!");
+            }
+            std::ptr::write(
+                ptr,
+                Self {
+                    key_ordering: PhantomData,
+                    key_segments: PhantomData,
+                    rc: atomic::AtomicU32::new(1),
+                    end_depth: end_depth as u32,
+                    childleaf: children[0].childleaf(),
+                    leaf_count: children.iter().map(|child| child.count()).sum(),
+                    segment_count: children
+                        .iter()
+                        .map(|child| child.count_segment(end_depth))
+                        .sum(),
+                    hash: children.iter().fold(0, |hash, child| hash ^ child.hash()),
+                    child_table: std::array::from_fn(|_| None),
+                },
+            );
+            ptr
+        }
+    }
+
     pub(super) unsafe fn rc_inc(branch: *mut Self) -> *mut Self {
         unsafe {
             let mut current = (*branch).rc.load(Relaxed);
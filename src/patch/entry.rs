@@ -6,6 +6,9 @@ pub struct Entry<const KEY_LEN: usize> {
     ptr: *mut Leaf<KEY_LEN>,
 }
 
+unsafe impl<const KEY_LEN: usize> Send for Entry<KEY_LEN> {}
+unsafe impl<const KEY_LEN: usize> Sync for Entry<KEY_LEN> {}
+
 impl<const KEY_LEN: usize> Entry<KEY_LEN> {
     pub fn new(key: &[u8; KEY_LEN]) -> Self {
         unsafe {
@@ -14,6 +17,10 @@ impl<const KEY_LEN: usize> Entry<KEY_LEN> {
         }
     }
 
+    pub(super) fn key(&self) -> &[u8; KEY_LEN] {
+        unsafe { &(*self.ptr).key }
+    }
+
     pub(super) fn leaf<O: KeyOrdering<KEY_LEN>, S: KeySegmentation<KEY_LEN>>(
         &self,
     ) -> Head<KEY_LEN, O, S> {
@@ -8,6 +8,14 @@ use std::convert::TryInto;
 
 use super::*;
 
+/// The hash of a leaf holding `key`. Branch hashes are the XOR of their
+/// children, so the root hash of a PATCH is the XOR of these over all keys.
+pub(crate) fn key_hash(key: &[u8]) -> u128 {
+    let mut hasher = SipHasher24::new_with_key(sip_key());
+    hasher.write(key);
+    hasher.finish128().into()
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub(crate) struct Leaf<const KEY_LEN: usize> {
@@ -18,15 +26,14 @@ pub(crate) struct Leaf<const KEY_LEN: usize> {
 
 impl<const KEY_LEN: usize> Leaf<KEY_LEN> {
     pub(super) unsafe fn new(key: &[u8; KEY_LEN]) -> *mut Self {
+        init();
         unsafe {
             let layout = Layout::new::<Self>();
             let ptr = alloc(layout) as *mut Self;
             if ptr.is_null() {
                 panic!("Allocation failed!");
             }
-            let mut hasher = SipHasher24::new_with_key(&SIP_KEY);
-            hasher.write(&key[..]);
-            let hash = hasher.finish128().into();
+            let hash = key_hash(key);
 
             std::ptr::write(
                 ptr,
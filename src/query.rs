@@ -9,22 +9,109 @@
 //! providing great flexibililty in the way different query operators,
 //! sub-languages, and data-sources can be composed.
 //!
+//! # Ordering
 //!
+//! The engine tries the values of a variable in the order in which the
+//! proposing constraint returns them. The PATCH based constraints, like
+//! [crate::TribleSet] patterns, propose values in ascending byte order of
+//! their [Value] representation, so the values of a multi-valued attribute
+//! are returned in ascending byte order when the entity and attribute are
+//! bound. Other constraints may propose in any order, e.g. hash set order,
+//! use [Query::order_by] to enforce an order independent of the proposing
+//! constraint.
+//!
+//! # Joining datasets
+//!
+//! Constraints over different datasets can be combined in a single query,
+//! as long as they are created from the same [VariableContext].
+//! Every constraint reports an estimate of how many values it would propose
+//! for a variable, which for [crate::TribleSet] patterns is the number of
+//! distinct values below the currently bound prefix. The engine always lets
+//! the constraint with the smallest estimate propose, so a tiny request-scoped
+//! set joined against a large persistent one drives the search, and the large
+//! set only has to confirm the handful of candidates.
+//!
+//! ```
+//! use std::convert::TryInto;
+//! use tribles::{and, find, types::ShortString, ufoid, Id, TribleSet, NS};
+//!
+//! NS! {
+//!     pub namespace knights {
+//!         "328edd7583de04e2bedd6bd4fd50e651" as loves: tribles::Id;
+//!         "328147856cc1984f0806dbb824d2b4cb" as name: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! NS! {
+//!     pub namespace request {
+//!         "9b0dd4c3c2d3af4f25a4ee0b2c1e1b3e" as reason: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! let romeo = ufoid();
+//! let juliet = ufoid();
+//!
+//! let mut persistent = TribleSet::new();
+//! persistent.union(knights::entity!(romeo, {
+//!     name: "Romeo".try_into().unwrap(),
+//!     loves: juliet
+//! }));
+//! persistent.union(knights::entity!(juliet, {
+//!     name: "Juliet".try_into().unwrap(),
+//!     loves: romeo
+//! }));
+//!
+//! let mut candidates = TribleSet::new();
+//! candidates.union(request::entity!(juliet, {
+//!     reason: "recently active".try_into().unwrap()
+//! }));
+//!
+//! let r: Vec<_> = find!(
+//!     ctx,
+//!     (person, name, reason),
+//!     and!(
+//!         request::pattern!(ctx, candidates, [{person @ reason: reason}]),
+//!         knights::pattern!(ctx, persistent, [{person @ name: name}])
+//!     )
+//! )
+//! .collect();
+//!
+//! assert_eq!(
+//!     vec![Ok((
+//!         juliet,
+//!         "Juliet".try_into().unwrap(),
+//!         "recently active".try_into().unwrap()
+//!     ))],
+//!     r
+//! );
+//! ```
+pub mod compute;
 pub mod constantconstraint;
 pub mod hashsetconstraint;
+pub mod index;
 pub mod intersectionconstraint;
 pub mod mask;
+pub mod negation;
+pub mod parallel;
 pub mod patchconstraint;
+pub mod patternbuilder;
+pub mod sample;
+pub mod stats;
 
+use std::cell::RefCell;
+use std::error::Error;
 use std::fmt;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
 
+pub use compute::*;
 pub use constantconstraint::*;
 pub use hashsetconstraint::*;
 pub use intersectionconstraint::*;
 pub use mask::*;
+pub use negation::*;
 pub use patchconstraint::*;
+pub use patternbuilder::PatternBuilder;
 
 use crate::{Id, Value, ValueParseError, Valuelike};
 
@@ -110,6 +197,12 @@ pub trait ContainsConstraint<'a, T> {
     fn has(&'a self, v: Variable<T>) -> Self::Constraint;
 }
 
+impl<T> fmt::Display for Variable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "?{}", self.index)
+    }
+}
+
 impl<T> Variable<T> {
     pub fn is(self, constant: T) -> ConstantConstraint<T>
     where
@@ -119,13 +212,56 @@ impl<T> Variable<T> {
     }
 }
 
+/// The error of a query result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QueryError {
+    /// A bound value couldn't be converted to the type of its variable.
+    Parse(ValueParseError),
+    /// A constraint failed for the binding of the result, e.g. the closure
+    /// of a [ComputeConstraint] panicked with this message.
+    Failed(String),
+}
+
+impl From<ValueParseError> for QueryError {
+    fn from(err: ValueParseError) -> Self {
+        QueryError::Parse(err)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => err.fmt(f),
+            Self::Failed(msg) => write!(f, "constraint failed: {}", msg),
+        }
+    }
+}
+
+impl Error for QueryError {}
+
 #[derive(Clone, Debug)]
 pub struct Binding {
     pub bound: VariableSet,
     values: [Value; 256],
+    failures: RefCell<Vec<QueryError>>,
 }
 
 impl Binding {
+    /// Reports that a constraint failed for this binding.
+    ///
+    /// Constraints only get shared access to the binding, so the failure is
+    /// recorded on the side. The query returns it as an error result, in
+    /// place of the results the constraint couldn't produce.
+    pub fn fail(&self, error: QueryError) {
+        self.failures.borrow_mut().push(error);
+    }
+
+    /// Takes the failures reported with [Binding::fail].
+    pub(crate) fn take_failures(&self) -> Vec<QueryError> {
+        std::mem::take(&mut *self.failures.borrow_mut())
+    }
+
     pub fn set(&mut self, variable: VariableId, value: Value) {
         self.values[variable as usize] = value;
         self.bound.set(variable);
@@ -149,6 +285,7 @@ impl Default for Binding {
         Self {
             bound: ByteBitset::new_empty(),
             values: [[0; 32]; 256],
+            failures: RefCell::new(Vec::new()),
         }
     }
 }
@@ -159,11 +296,76 @@ pub trait Constraint<'a> {
     fn estimate(&self, variable: VariableId, binding: &Binding) -> usize;
     fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value>;
     fn confirm(&self, variable: VariableId, binding: &Binding, proposal: &mut Vec<Value>);
+
+    /// Describes the constraint and its sub-constraints for debugging, see
+    /// [dump_constraint]. The default lists the type and the variables of
+    /// the constraint.
+    fn describe(&self) -> ConstraintDescription {
+        let variables: Vec<String> = self
+            .variables()
+            .into_iter()
+            .map(|v| format!("?{}", v))
+            .collect();
+        ConstraintDescription::new(format!(
+            "{} [{}]",
+            std::any::type_name::<Self>(),
+            variables.join(", ")
+        ))
+    }
+}
+
+/// A tree of labels describing a constraint, as returned by
+/// [Constraint::describe].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintDescription {
+    pub label: String,
+    pub children: Vec<ConstraintDescription>,
+}
+
+impl ConstraintDescription {
+    pub fn new(label: impl Into<String>) -> Self {
+        ConstraintDescription {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_children(label: impl Into<String>, children: Vec<ConstraintDescription>) -> Self {
+        ConstraintDescription {
+            label: label.into(),
+            children,
+        }
+    }
+
+    fn render(&self, depth: usize, out: &mut String) {
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(&self.label);
+        out.push('\n');
+        for child in &self.children {
+            child.render(depth + 1, out);
+        }
+    }
+}
+
+/// Renders the description of `constraint` as an indented tree, with one
+/// line per constraint.
+pub fn dump_constraint<'a>(constraint: &dyn Constraint<'a>) -> String {
+    let mut out = String::new();
+    constraint.describe().render(0, &mut out);
+    out
+}
+
+/// The order in which a [Query] enumerates the values of a variable, see
+/// [Query::order_by].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ascending,
+    Descending,
 }
 
 pub struct State {
     variable: VariableId,
-    values: Vec<Value>,
+    values: std::vec::IntoIter<Value>,
 }
 pub struct Query<C, P: Fn(&Binding) -> Result<R, ValueParseError>, R> {
     constraint: C,
@@ -172,6 +374,9 @@ pub struct Query<C, P: Fn(&Binding) -> Result<R, ValueParseError>, R> {
     binding: Binding,
     stack: Vec<State>,
     unbound: Vec<VariableId>,
+    order: Vec<(VariableId, Direction)>,
+    remaining: Option<usize>,
+    failures: Vec<QueryError>,
 }
 
 impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> Query<C, P, R> {
@@ -184,8 +389,76 @@ impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> Qu
             binding: Default::default(),
             stack: Vec::new(),
             unbound: Vec::from_iter(variables),
+            order: Vec::new(),
+            remaining: None,
+            failures: Vec::new(),
         }
     }
+
+    /// Stops the search after `limit` results.
+    ///
+    /// Unlike [Iterator::take], the query releases its search state as soon
+    /// as the limit is reached, instead of keeping it until it is dropped.
+    pub fn take_limited(mut self, limit: usize) -> Self {
+        self.remaining = Some(limit);
+        if limit == 0 {
+            self.finish();
+        }
+        self
+    }
+
+    fn finish(&mut self) {
+        self.mode = Search::Done;
+        self.stack = Vec::new();
+        self.unbound = Vec::new();
+        self.failures = Vec::new();
+    }
+
+    fn emit(&mut self, result: Result<R, QueryError>) -> Option<Result<R, QueryError>> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.finish();
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns the results ordered by the value of `variable`, after the
+    /// orderings added before.
+    ///
+    /// Ordered variables are bound first and their values are enumerated
+    /// in order, so the results are streamed without a terminal sort, but
+    /// the engine can't pick a cheaper variable to start with. Values are
+    /// compared by their byte representation, which matches the natural
+    /// order for e.g. strings, ids and non-negative big-endian numbers.
+    pub fn order_by(mut self, variable: VariableId, direction: Direction) -> Self {
+        assert!(
+            self.stack.is_empty() && self.unbound.contains(&variable),
+            "ordered variables must be constrained and not yet bound"
+        );
+        self.order.retain(|&(v, _)| v != variable);
+        self.order.push((variable, direction));
+        self
+    }
+
+    /// The values of `variable` in the order in which they are tried, which
+    /// is the order the constraint proposed them in, unless the variable is
+    /// ordered.
+    fn propose(
+        &mut self,
+        variable: VariableId,
+        direction: Option<Direction>,
+    ) -> std::vec::IntoIter<Value> {
+        let mut values = self.constraint.propose(variable, &self.binding);
+        self.failures.extend(self.binding.take_failures());
+        match direction {
+            None => {}
+            Some(Direction::Ascending) => values.sort_unstable(),
+            Some(Direction::Descending) => values.sort_unstable_by(|a, b| b.cmp(a)),
+        }
+        values.into_iter()
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -200,7 +473,7 @@ impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> It
     for Query<C, P, R>
 {
     // we will be counting with usize
-    type Item = Result<R, ValueParseError>;
+    type Item = Result<R, QueryError>;
 
     // next() is the only required method
     fn next(&mut self) -> Option<Self::Item> {
@@ -209,15 +482,28 @@ impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> It
                 Search::Vertical => {
                     self.mode = Search::Horizontal;
 
+                    // Ordered variables occupy the bottom of the stack.
+                    if let Some(&(next_variable, direction)) = self.order.get(self.stack.len()) {
+                        self.unbound.retain(|&v| v != next_variable);
+                        let values = self.propose(next_variable, Some(direction));
+                        self.stack.push(State {
+                            variable: next_variable,
+                            values,
+                        });
+                        continue;
+                    }
+
                     match self.unbound.len() {
                         0 => {
-                            return Some((self.postprocessing)(&self.binding));
+                            let result = (self.postprocessing)(&self.binding);
+                            return self.emit(result.map_err(QueryError::from));
                         }
                         1 => {
                             let next_variable = self.unbound.pop().unwrap();
+                            let values = self.propose(next_variable, None);
                             self.stack.push(State {
                                 variable: next_variable,
-                                values: self.constraint.propose(next_variable, &self.binding),
+                                values,
                             })
                         }
                         _ => {
@@ -228,16 +514,20 @@ impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> It
                                 .min_by_key(|(_, &v)| self.constraint.estimate(v, &self.binding))
                                 .unwrap();
                             self.unbound.swap_remove(index);
+                            let values = self.propose(next_variable, None);
                             self.stack.push(State {
                                 variable: next_variable,
-                                values: self.constraint.propose(next_variable, &self.binding),
+                                values,
                             });
                         }
                     }
                 }
                 Search::Horizontal => {
+                    if let Some(error) = self.failures.pop() {
+                        return self.emit(Err(error));
+                    }
                     if let Some(state) = self.stack.last_mut() {
-                        if let Some(assignment) = state.values.pop() {
+                        if let Some(assignment) = state.values.next() {
                             self.binding.set(state.variable, assignment);
                             self.mode = Search::Vertical;
                         } else {
@@ -274,8 +564,30 @@ impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> fm
     }
 }
 
+/// Finds all bindings of the projected variables that satisfy the
+/// constraint.
+///
+/// An optional `order_by: (a asc, b desc)` clause streams the results
+/// ordered by the listed variables, see [Query::order_by].
 #[macro_export]
 macro_rules! find {
+    (@direction asc) => {
+        $crate::query::Direction::Ascending
+    };
+    (@direction desc) => {
+        $crate::query::Direction::Descending
+    };
+    ($ctx:ident, ($($Var:ident),+), order_by: ($($Ordered:ident $Direction:ident),+ $(,)?), $Constraint:expr) => {
+        {
+            let mut $ctx = $crate::query::VariableContext::new();
+            $(let $Var = $ctx.next_variable();)*
+              $crate::query::Query::new($Constraint,
+                move |binding| {
+                    Ok(($($Var.extract(binding)?),+,))
+            })
+            $(.order_by($Ordered.index, $crate::find!(@direction $Direction)))+
+        }
+    };
     ($ctx:ident, ($($Var:ident),+), $Constraint:expr) => {
         {
             let mut $ctx = $crate::query::VariableContext::new();
@@ -289,15 +601,137 @@ macro_rules! find {
 }
 pub use find;
 
+/// Like [find], but returns only the first result, if there is one.
+///
+/// The search stops as soon as the first result is found, see
+/// [Query::take_limited].
+///
+/// ```
+/// use std::convert::TryInto;
+/// use tribles::{find_first, types::ShortString, ufoid, NS};
+///
+/// NS! {
+///     pub namespace books {
+///         "3A8C5E7B9D1F2A4C6E8B0D2F4A6C8E0B" as title: tribles::types::ShortString;
+///     }
+/// }
+///
+/// let dune = ufoid();
+/// let kb = books::entity!(dune, { title: "Dune".try_into().unwrap() });
+///
+/// let found = find_first!(
+///     ctx,
+///     (book),
+///     books::pattern!(ctx, kb, [{book @ title: ("Dune".try_into().unwrap())}])
+/// );
+/// assert_eq!(found.unwrap().unwrap(), (dune,));
+///
+/// let missing = find_first!(
+///     ctx,
+///     (book),
+///     books::pattern!(ctx, kb, [{book @ title: ("Emma".try_into().unwrap())}])
+/// );
+/// assert!(missing.is_none());
+/// ```
+#[macro_export]
+macro_rules! find_first {
+    ($($Query:tt)*) => {
+        $crate::find!($($Query)*).take_limited(1).next()
+    };
+}
+pub use find_first;
+
+/// Like [find], but collects every result row into a struct.
+///
+/// The fields listed in the struct pattern are the query variables, so
+/// the projection and the construction of the struct can't get out of
+/// sync. The types of the variables are inferred from the field types
+/// and can optionally be spelled out.
+///
+/// ```
+/// use std::convert::TryInto;
+/// use tribles::{find_as, types::ShortString, ufoid, Id, NS};
+///
+/// NS! {
+///     pub namespace knights {
+///         "9E2A5B7C1D3F4E6A8B0C2D4E6F8A0B1C" as name: tribles::types::ShortString;
+///     }
+/// }
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Knight {
+///     id: Id,
+///     name: ShortString,
+/// }
+///
+/// let romeo = ufoid();
+/// let kb = knights::entity!(romeo, { name: "Romeo".try_into().unwrap() });
+///
+/// let knights: Vec<Knight> = find_as!(
+///     ctx,
+///     Knight { id, name: ShortString },
+///     knights::pattern!(ctx, kb, [{id @ name: name}])
+/// )
+/// .map(|r| r.unwrap())
+/// .collect();
+///
+/// assert_eq!(knights, vec![Knight { id: romeo, name: "Romeo".try_into().unwrap() }]);
+/// ```
+///
+/// Only the listed fields are declared as variables, so a constraint that
+/// uses any other name is rejected:
+///
+/// ```compile_fail
+/// use std::convert::TryInto;
+/// use tribles::{find_as, types::ShortString, ufoid, Id, NS};
+///
+/// NS! {
+///     pub namespace knights {
+///         "9E2A5B7C1D3F4E6A8B0C2D4E6F8A0B1C" as name: tribles::types::ShortString;
+///     }
+/// }
+///
+/// struct Knight {
+///     id: Id,
+///     name: ShortString,
+/// }
+///
+/// let romeo = ufoid();
+/// let kb = knights::entity!(romeo, { name: "Romeo".try_into().unwrap() });
+///
+/// let knights = find_as!(
+///     ctx,
+///     Knight { id, name: ShortString },
+///     knights::pattern!(ctx, kb, [{id @ name: title}])
+/// );
+/// ```
+#[macro_export]
+macro_rules! find_as {
+    ($ctx:ident, $Struct:ident { $($Field:ident $(: $Type:ty)?),+ $(,)? }, $Constraint:expr) => {
+        {
+            let mut $ctx = $crate::query::VariableContext::new();
+            $(let $Field $(: $crate::query::Variable<$Type>)? = $ctx.next_variable();)*
+              $crate::query::Query::new($Constraint,
+                move |binding| {
+                    Ok($Struct { $($Field: $Field.extract(binding)?),+ })
+            })
+        }
+    };
+}
+pub use find_as;
+
 #[cfg(test)]
 mod tests {
     //use fake::faker::name::raw::*;
     //use fake::locales::*;
     //use fake::{Dummy, Fake, Faker};
-    use std::{collections::HashSet, convert::TryInto};
+    use std::{cell::Cell, collections::HashSet, convert::TryInto};
 
     //use crate::tribleset::patchtribleset::PATCHTribleSet;
-    use crate::{types::ShortString, ufoid, Id, TribleSet, NS};
+    use crate::{
+        types::{NsTAIInterval, ShortString},
+        ufoid, Id, TribleSet, NS,
+    };
 
     use super::*;
 
@@ -308,6 +742,41 @@ mod tests {
         }
     }
 
+    NS! {
+        pub namespace request {
+            "A7E21C3AE8A0F6C87C9A2A0AE3F1F1B2" as reason: ShortString;
+        }
+    }
+
+    struct ProposalCounter<'a> {
+        constraint: Box<dyn Constraint<'a> + 'a>,
+        proposed: &'a Cell<usize>,
+    }
+
+    impl<'a> Constraint<'a> for ProposalCounter<'a> {
+        fn variables(&self) -> VariableSet {
+            self.constraint.variables()
+        }
+
+        fn variable(&self, variable: VariableId) -> bool {
+            self.constraint.variable(variable)
+        }
+
+        fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+            self.constraint.estimate(variable, binding)
+        }
+
+        fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+            let proposal = self.constraint.propose(variable, binding);
+            self.proposed.set(self.proposed.get() + proposal.len());
+            proposal
+        }
+
+        fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+            self.constraint.confirm(variable, binding, proposals)
+        }
+    }
+
     #[test]
     fn and_set() {
         let mut books = HashSet::new();
@@ -389,4 +858,297 @@ mod tests {
 
         assert_eq!(1, r.len())
     }
+
+    #[test]
+    fn cross_set_join() {
+        let mut persistent = TribleSet::new();
+        let mut ephemeral = TribleSet::new();
+
+        for i in 0..10000 {
+            let lover_a = ufoid();
+            let lover_b = ufoid();
+            persistent.union(knights::entity!(lover_a, {
+                name: "Romeo".try_into().unwrap(),
+                loves: lover_b
+            }));
+            persistent.union(knights::entity!(lover_b, {
+                name: "Juliet".try_into().unwrap(),
+                loves: lover_a
+            }));
+            if i % 1000 == 0 {
+                ephemeral.union(request::entity!(lover_a, {
+                    reason: "candidate".try_into().unwrap()
+                }));
+            }
+        }
+        assert_eq!(ephemeral.len(), 10);
+
+        let proposed = Cell::new(0);
+        let r: Vec<_> = find!(
+            ctx,
+            (person, name, beloved, reason),
+            ProposalCounter {
+                constraint: Box::new(and!(
+                    knights::pattern!(ctx, persistent, [
+                        {person @ name: name, loves: beloved}]),
+                    request::pattern!(ctx, ephemeral, [
+                        {person @ reason: reason}])
+                )),
+                proposed: &proposed,
+            }
+        )
+        .collect();
+
+        assert_eq!(10, r.len());
+        // The entity candidates must come from the small set, so the total
+        // number of proposals is bounded by its size and not by the 20000
+        // entities in the persistent set.
+        assert!(proposed.get() < 100);
+    }
+
+    #[test]
+    fn limit_stops_early() {
+        let mut kb = TribleSet::new();
+        for _ in 0..1000 {
+            let (lover_a, lover_b) = (ufoid(), ufoid());
+            kb.union(knights::entity!(lover_a, {
+                name: "Romeo".try_into().unwrap(),
+                loves: lover_b
+            }));
+            kb.union(knights::entity!(lover_b, {
+                name: "Juliet".try_into().unwrap(),
+                loves: lover_a
+            }));
+        }
+
+        let count = |limit: Option<usize>| {
+            let proposed = Cell::new(0);
+            let query = find!(
+                ctx,
+                (person, name, beloved),
+                ProposalCounter {
+                    constraint: Box::new(knights::pattern!(ctx, kb, [
+                        {person @ name: name, loves: beloved}])),
+                    proposed: &proposed,
+                }
+            );
+            let results = match limit {
+                Some(limit) => query.take_limited(limit).count(),
+                None => query.count(),
+            };
+            (results, proposed.get())
+        };
+
+        let (all, all_proposed) = count(None);
+        let (first, first_proposed) = count(Some(1));
+        let (none, none_proposed) = count(Some(0));
+        assert_eq!((all, first, none), (2000, 1, 0));
+        assert_eq!(none_proposed, 0);
+        // Only the proposals for the first entity candidate are explored,
+        // instead of those of all 2000 entities.
+        assert!(first_proposed * 2 < all_proposed);
+
+        let name: Option<Result<(Id, ShortString), QueryError>> = find_first!(
+            ctx,
+            (person, name),
+            knights::pattern!(ctx, kb, [{person @ name: name}])
+        );
+        assert!(name.unwrap().is_ok());
+    }
+
+    #[test]
+    fn multi_values_are_ordered() {
+        let knight = ufoid();
+        let mut kb = TribleSet::new();
+        for i in 0..100 {
+            kb.union(knights::entity!(knight, {
+                name: (&format!("Knight {}", i)[..]).try_into().unwrap()
+            }));
+            kb.union(knights::entity!({
+                name: (&format!("Other {}", i)[..]).try_into().unwrap()
+            }));
+        }
+
+        let indices = kb.indices();
+        let indexed: Vec<Value> = indices
+            .eav
+            .iter_prefix::<64>()
+            .map(|(t, _)| t)
+            .filter(|t| t[0..16] == knight)
+            .map(|t| t[32..64].try_into().unwrap())
+            .collect();
+
+        let bound: Vec<Value> = find!(
+            ctx,
+            (name),
+            knights::pattern!(ctx, kb, [{(knight) @ name: name}])
+        )
+        .map(|r| {
+            let name: ShortString = r.unwrap().0;
+            Valuelike::into_value(&name)
+        })
+        .collect();
+
+        let joined: Vec<Value> = find!(
+            ctx,
+            (e, name),
+            and!(
+                e.is(knight),
+                knights::pattern!(ctx, kb, [{e @ name: name}])
+            )
+        )
+        .map(|r| {
+            let (_, name): (Id, ShortString) = r.unwrap();
+            Valuelike::into_value(&name)
+        })
+        .collect();
+
+        let viewed = kb.entity(knight).unwrap().values(knights::ids::name);
+
+        let mut infixes = vec![];
+        let mut prefix = [0; 32];
+        prefix[0..16].copy_from_slice(&knight);
+        prefix[16..32].copy_from_slice(&knights::ids::name);
+        indices
+            .eav
+            .infixes::<32, 32, _>(&prefix, |v: Value| infixes.push(v));
+
+        let mut unordered = vec![];
+        indices
+            .eav
+            .infixes_unordered::<32, 32, _>(&prefix, |v: Value| unordered.push(v));
+
+        let mut sorted = indexed.clone();
+        sorted.sort();
+        unordered.sort();
+
+        assert_eq!(indexed.len(), 100);
+        assert_eq!(indexed, sorted);
+        assert_eq!(bound, sorted);
+        assert_eq!(joined, sorted);
+        assert_eq!(viewed, sorted);
+        assert_eq!(infixes, sorted);
+        assert_eq!(unordered, sorted);
+    }
+
+    #[test]
+    fn dump() {
+        let kb = knights::entity!(ufoid(), { name: "Romeo".try_into().unwrap() });
+
+        let mut ctx = VariableContext::new();
+        let e: Variable<Id> = ctx.next_variable();
+        let constraint = knights::pattern!(ctx, kb, [{e @ name: ("Romeo".try_into().unwrap())}]);
+
+        assert_eq!(
+            dump_constraint(&constraint),
+            concat!(
+                "and\n",
+                "  ?1 is 00000000000000000000000000000000D6E0F2A6E5214E1330565B4D4138E55C\n",
+                "  ?2 is 526F6D656F000000000000000000000000000000000000000000000000000000\n",
+                "  tribleset ?0 ?1 ?2 (1 tribles)\n"
+            )
+        );
+
+        let proposed = Cell::new(0);
+        let counter = ProposalCounter {
+            constraint: Box::new(constraint),
+            proposed: &proposed,
+        };
+        let dump = dump_constraint(&counter);
+        assert!(dump.contains("ProposalCounter"));
+        assert!(dump.ends_with(" [?0, ?1, ?2]\n"));
+    }
+
+    NS! {
+        pub namespace events {
+            "5C1E9A7B3D2F4068A1B3C5D7E9F10234" as who: ShortString;
+            "6D2F0B8C4E3A5179B2C4D6E8F0A21345" as at: NsTAIInterval;
+        }
+    }
+
+    #[test]
+    fn order_by() {
+        let entries = [
+            ("Tybalt", 30),
+            ("Benvolio", 10),
+            ("Romeo", 50),
+            ("Benvolio", 40),
+            ("Mercutio", 20),
+            ("Romeo", 5),
+        ];
+        let mut kb = TribleSet::new();
+        for (who, at) in entries {
+            kb.union(events::entity!(ufoid(), {
+                who: who.try_into().unwrap(),
+                at: NsTAIInterval(at, at)
+            }));
+        }
+
+        let by_time: Vec<i128> = find!(
+            ctx,
+            (e, at),
+            order_by: (at asc),
+            events::pattern!(ctx, kb, [{e @ at: at}])
+        )
+        .map(|r: Result<(Id, NsTAIInterval), QueryError>| r.unwrap().1 .0)
+        .collect();
+        assert_eq!(by_time, vec![5, 10, 20, 30, 40, 50]);
+
+        type Row = (Id, ShortString, NsTAIInterval);
+        let by_name: Vec<(String, i128)> = find!(
+            ctx,
+            (e, who, at),
+            order_by: (who desc, at asc),
+            events::pattern!(ctx, kb, [{e @ who: who, at: at}])
+        )
+        .map(|r: Result<Row, QueryError>| {
+            let (_, who, at) = r.unwrap();
+            let who: &str = (&who).into();
+            (who.to_owned(), at.0)
+        })
+        .collect();
+        let mut expected: Vec<(String, i128)> = entries
+            .iter()
+            .map(|&(who, at)| (who.to_owned(), at))
+            .collect();
+        expected.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        assert_eq!(by_name, expected);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Lover<L> {
+        lover: Id,
+        name: ShortString,
+        loves: L,
+    }
+
+    #[test]
+    fn find_as() {
+        let romeo = ufoid();
+        let juliet = ufoid();
+        let mut kb = knights::entity!(romeo, {
+            name: "Romeo".try_into().unwrap(),
+            loves: juliet
+        });
+        kb.union(knights::entity!(juliet, {
+            name: "Juliet".try_into().unwrap()
+        }));
+
+        let lovers: Vec<Lover<Id>> = find_as!(
+            ctx,
+            Lover { lover, name, loves: Id },
+            knights::pattern!(ctx, kb, [{lover @ name: name, loves: loves}])
+        )
+        .map(|r| r.unwrap())
+        .collect();
+
+        assert_eq!(
+            lovers,
+            vec![Lover {
+                lover: romeo,
+                name: "Romeo".try_into().unwrap(),
+                loves: juliet
+            }]
+        );
+    }
 }
@@ -0,0 +1,324 @@
+//! Values derived from other variables during a query.
+//!
+//! A [ComputeConstraint] binds an output variable to the result of a
+//! function of some input variables, e.g. a full name from a first and a
+//! last name. Once the inputs are bound it proposes the computed value.
+//! If the output is bound by another constraint first, e.g. to a constant,
+//! it only confirms inputs that compute to that value, so the derived value
+//! can be used to filter inside the query.
+//!
+//! The inputs have to be bound by other constraints, a computation can't
+//! be inverted. The [compute] macro takes care of extracting typed inputs.
+
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use super::{
+    Binding, Constraint, ConstraintDescription, QueryError, Variable, VariableId, VariableSet,
+};
+use crate::{Value, Valuelike};
+
+pub struct ComputeConstraint<'a> {
+    inputs: VariableSet,
+    output: VariableId,
+    compute: Box<dyn Fn(&Binding) -> Option<Value> + 'a>,
+}
+
+impl<'a> ComputeConstraint<'a> {
+    /// Binds `output` to the result of `compute` once all `inputs` are bound.
+    ///
+    /// Bindings for which `compute` returns `None` have no output value and
+    /// are thus excluded from the results. Panics in `compute` are caught
+    /// and reported with [Binding::fail], so the query returns them as
+    /// [QueryError::Failed] results instead of unwinding. The panic hook
+    /// still runs, i.e. the panic message is printed as usual.
+    pub fn new<O, F>(inputs: VariableSet, output: Variable<O>, compute: F) -> Self
+    where
+        O: Valuelike,
+        F: Fn(&Binding) -> Option<O> + 'a,
+    {
+        ComputeConstraint {
+            inputs,
+            output: output.index,
+            compute: Box::new(move |binding| compute(binding).map(|o| O::into_value(&o))),
+        }
+    }
+
+    fn run(&self, binding: &Binding) -> Result<Option<Value>, QueryError> {
+        catch_unwind(AssertUnwindSafe(|| (self.compute)(binding)))
+            .map_err(|payload| QueryError::Failed(panic_message(payload)))
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "computation panicked".to_owned()
+    }
+}
+
+impl<'a> Constraint<'a> for ComputeConstraint<'a> {
+    fn variables(&self) -> VariableSet {
+        let mut variables = self.inputs;
+        variables.set(self.output);
+        variables
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.output == variable || self.inputs.is_set(variable)
+    }
+
+    fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+        if variable == self.output && binding.bound.is_superset_of(&self.inputs) {
+            1
+        } else {
+            usize::MAX
+        }
+    }
+
+    fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+        if variable == self.output && binding.bound.is_superset_of(&self.inputs) {
+            match self.run(binding) {
+                Ok(value) => value.into_iter().collect(),
+                Err(err) => {
+                    binding.fail(err);
+                    vec![]
+                }
+            }
+        } else {
+            vec![]
+        }
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+        if variable == self.output {
+            if binding.bound.is_superset_of(&self.inputs) {
+                match self.run(binding) {
+                    Ok(computed) => proposals.retain(|v| Some(*v) == computed),
+                    Err(err) => {
+                        binding.fail(err);
+                        proposals.clear();
+                    }
+                }
+            }
+            return;
+        }
+
+        let mut pending = self.inputs;
+        pending.unset(variable);
+        pending.set(self.output);
+        if !binding.bound.is_superset_of(&pending) {
+            // Checked once the output and the remaining inputs are bound.
+            return;
+        }
+        let output = binding.get(self.output);
+        let mut candidate = binding.clone();
+        proposals.retain(|&value| {
+            candidate.set(variable, value);
+            match self.run(&candidate) {
+                Ok(computed) => computed == output,
+                Err(err) => {
+                    binding.fail(err);
+                    false
+                }
+            }
+        });
+    }
+
+    fn describe(&self) -> ConstraintDescription {
+        let inputs: Vec<String> = self.inputs.into_iter().map(|v| format!("?{}", v)).collect();
+        ConstraintDescription::new(format!(
+            "compute ?{} from [{}]",
+            self.output,
+            inputs.join(", ")
+        ))
+    }
+}
+
+/// Creates a [ComputeConstraint] that binds `$Output` to the result of a
+/// closure over the values of the input variables, which are passed to the
+/// closure parameters in order.
+///
+/// ```
+/// use std::convert::TryInto;
+/// use tribles::{and, compute, find, types::ShortString, ufoid, NS};
+///
+/// NS! {
+///     pub namespace people {
+///         "2E9B4C0A7D1F3E5A6B8C9D0E1F2A3B4C" as first: tribles::types::ShortString;
+///         "3F0C5D1B8E2A4F6B7C9D0E1F2A3B4C5D" as last: tribles::types::ShortString;
+///     }
+/// }
+///
+/// let kb = people::entity!(ufoid(), {
+///     first: "Frank".try_into().unwrap(),
+///     last: "Herbert".try_into().unwrap()
+/// });
+///
+/// let names: Vec<_> = find!(
+///     ctx,
+///     (first, last, full),
+///     and!(
+///         people::pattern!(ctx, kb, [{first: first, last: last}]),
+///         compute!(full = |first: ShortString, last: ShortString| {
+///             let (first, last): (&str, &str) = ((&first).into(), (&last).into());
+///             ShortString::new(format!("{} {}", first, last)).unwrap()
+///         }, from first, last)
+///     )
+/// )
+/// .map(|r| r.unwrap().2)
+/// .collect();
+///
+/// let expected: ShortString = "Frank Herbert".try_into().unwrap();
+/// assert_eq!(names, vec![expected]);
+/// ```
+#[macro_export]
+macro_rules! compute {
+    ($Output:ident = |$($Arg:ident : $Type:ty),+| $Body:expr, from $($Input:ident),+ $(,)?) => {
+        {
+            let mut inputs = $crate::query::VariableSet::new_empty();
+            $(inputs.set($Input.index);)+
+            $crate::query::ComputeConstraint::new(inputs, $Output, move |binding| {
+                $(let $Arg: $Type = $Input.extract(binding).ok()?;)+
+                Some($Body)
+            })
+        }
+    };
+}
+
+pub use compute;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::{and, find, types::ShortString, ufoid, TribleSet, NS};
+
+    NS! {
+        pub namespace people {
+            "8A3D6F1C2B4E5D7A9C0B1E2D3F4A5C6B" as first: ShortString;
+            "9B4E7A2D3C5F6E8B0D1C2F3E4A5B6D7C" as last: ShortString;
+        }
+    }
+
+    fn full_name(first: &ShortString, last: &ShortString) -> ShortString {
+        let (first, last): (&str, &str) = (first.into(), last.into());
+        ShortString::new(format!("{} {}", first, last)).unwrap()
+    }
+
+    fn authors() -> TribleSet {
+        let mut kb = TribleSet::new();
+        for (first, last) in [
+            ("Frank", "Herbert"),
+            ("Ursula", "Le Guin"),
+            ("Iain", "Banks"),
+            ("Frank", "Miller"),
+        ] {
+            kb.union(people::entity!(ufoid(), {
+                first: first.try_into().unwrap(),
+                last: last.try_into().unwrap()
+            }));
+        }
+        kb
+    }
+
+    #[test]
+    fn derive_and_project() {
+        let kb = authors();
+        let mut names: Vec<String> = find!(
+            ctx,
+            (first, last, full),
+            and!(
+                people::pattern!(ctx, kb, [{first: first, last: last}]),
+                compute!(full = |first: ShortString, last: ShortString| full_name(&first, &last),
+                    from first, last)
+            )
+        )
+        .map(
+            |r: Result<(ShortString, ShortString, ShortString), QueryError>| {
+                format!("{:?}", r.unwrap().2)
+            },
+        )
+        .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "ShortString(\"Frank Herbert\")",
+                "ShortString(\"Frank Miller\")",
+                "ShortString(\"Iain Banks\")",
+                "ShortString(\"Ursula Le Guin\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn derive_and_filter() {
+        let kb = authors();
+        let wanted: ShortString = "Frank Herbert".try_into().unwrap();
+        let computed = Cell::new(0);
+        let counter = &computed;
+
+        let r: Vec<_> = find!(
+            ctx,
+            (first, last, full),
+            and!(
+                full.is(wanted.clone()),
+                people::pattern!(ctx, kb, [{first: first, last: last}]),
+                compute!(full = |first: ShortString, last: ShortString| {
+                    counter.set(counter.get() + 1);
+                    full_name(&first, &last)
+                }, from first, last)
+            )
+        )
+        .collect::<Vec<Result<(ShortString, ShortString, ShortString), QueryError>>>();
+
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].as_ref().unwrap().2, wanted);
+        // The bound full name is checked once per author instead of being
+        // proposed and joined afterwards.
+        assert_eq!(computed.get(), 4);
+    }
+
+    #[test]
+    fn panics_are_reported() {
+        let kb = authors();
+        let mut rows: Vec<Result<String, QueryError>> = find!(
+            ctx,
+            (first, last, full),
+            and!(
+                people::pattern!(ctx, kb, [{first: first, last: last}]),
+                compute!(full = |first: ShortString, last: ShortString| {
+                    let name: &str = (&first).into();
+                    if name == "Frank" {
+                        panic!("no Franks");
+                    }
+                    full_name(&first, &last)
+                }, from first, last)
+            )
+        )
+        .map(
+            |r: Result<(ShortString, ShortString, ShortString), QueryError>| {
+                r.map(|(_, _, full)| String::from(<&str>::from(&full)))
+            },
+        )
+        .collect();
+        rows.sort_by_key(|r| format!("{:?}", r));
+
+        let failed = || Err(QueryError::Failed("no Franks".to_owned()));
+        assert_eq!(
+            rows,
+            vec![
+                failed(),
+                failed(),
+                Ok("Iain Banks".to_owned()),
+                Ok("Ursula Le Guin".to_owned()),
+            ]
+        );
+    }
+}
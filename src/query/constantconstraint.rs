@@ -37,4 +37,12 @@ impl<'a, T> Constraint<'a> for ConstantConstraint<T> {
     fn confirm(&self, _variable: VariableId, _binding: &Binding, proposals: &mut Vec<Value>) {
         proposals.retain(|v| *v == self.constant);
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "{} is {}",
+            self.variable,
+            hex::encode_upper(self.constant)
+        ))
+    }
 }
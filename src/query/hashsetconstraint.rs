@@ -42,6 +42,14 @@ where
     fn confirm(&self, _variable: VariableId, _binding: &Binding, proposals: &mut Vec<Value>) {
         proposals.retain(|v| T::from_value(*v).map_or(false, |v| self.set.contains(&v)));
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "{} in set ({} values)",
+            self.variable,
+            self.set.len()
+        ))
+    }
 }
 
 impl<'a, T> ContainsConstraint<'a, T> for HashSet<T>
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+
+use crate::{
+    id_from_value, id_into_value,
+    patch::{Entry, KeyOrdering, KeySegmentation, PATCH},
+    Id, TribleSet, Value, Valuelike, ID_LEN, VALUE_LEN,
+};
+
+use super::{Binding, Constraint, ConstraintDescription, Variable, VariableId, VariableSet};
+
+/// Length of an [AttributeIndex] key, a value followed by an entity.
+pub const INDEX_KEY_LEN: usize = VALUE_LEN + ID_LEN;
+
+#[derive(Copy, Clone, Debug)]
+pub struct AttributeIndexSegmentation {}
+
+impl KeySegmentation<INDEX_KEY_LEN> for AttributeIndexSegmentation {
+    fn segment(depth: usize) -> usize {
+        match depth {
+            0..=31 => 0,
+            32..=47 => 1,
+            _ => panic!(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct EVOrder {}
+
+impl KeyOrdering<INDEX_KEY_LEN> for EVOrder {
+    fn tree_index(key_index: usize) -> usize {
+        match key_index {
+            d @ 0..=31 => d + ID_LEN,
+            d @ 32..=47 => d - VALUE_LEN,
+            _ => panic!(),
+        }
+    }
+
+    fn key_index(tree_index: usize) -> usize {
+        match tree_index {
+            d if d < ID_LEN => d + VALUE_LEN,
+            d => d - ID_LEN,
+        }
+    }
+}
+
+/// A value to entity index for a single attribute.
+///
+/// Where the [TribleSet] indices are shared by all attributes, an
+/// [AttributeIndex] only contains the tribles of one attribute,
+/// which makes point lookups by value, e.g. for unique external keys,
+/// independent of the size of the rest of the dataset.
+///
+/// The entities of each value are kept in a sorted vector, so that
+/// [AttributeIndex::lookup] can return them without allocating.
+///
+/// The index can be kept up to date with [AttributeIndex::update] as new
+/// tribles arrive. It only supports additions, so when a change removes
+/// tribles the index has to be rebuilt with [AttributeIndex::build].
+#[derive(Debug, Clone)]
+pub struct AttributeIndex {
+    attribute: Id,
+    ve: HashMap<Value, Vec<Id>>,
+    ev: PATCH<INDEX_KEY_LEN, EVOrder, AttributeIndexSegmentation>,
+}
+
+impl AttributeIndex {
+    pub fn new(attribute: Id) -> Self {
+        AttributeIndex {
+            attribute,
+            ve: HashMap::new(),
+            ev: PATCH::new(),
+        }
+    }
+
+    pub fn build(set: &TribleSet, attribute: Id) -> Self {
+        let mut index = AttributeIndex::new(attribute);
+        index.update(set);
+        index
+    }
+
+    /// Adds the tribles of `delta` that carry the indexed attribute.
+    pub fn update(&mut self, delta: &TribleSet) {
+        let ve = &mut self.ve;
+        let ev = &mut self.ev;
+        let attribute = self.attribute;
+        let delta = delta.indices();
+        delta
+            .ave
+            .infixes_unordered::<ID_LEN, VALUE_LEN, _>(&attribute, &mut |v: Value| {
+                let mut prefix = [0u8; ID_LEN + VALUE_LEN];
+                prefix[0..ID_LEN].copy_from_slice(&attribute);
+                prefix[ID_LEN..ID_LEN + VALUE_LEN].copy_from_slice(&v);
+                delta
+                    .ave
+                    .infixes_unordered::<{ ID_LEN + VALUE_LEN }, ID_LEN, _>(
+                        &prefix,
+                        &mut |e: Id| {
+                            let mut key = [0u8; INDEX_KEY_LEN];
+                            key[0..VALUE_LEN].copy_from_slice(&v);
+                            key[VALUE_LEN..INDEX_KEY_LEN].copy_from_slice(&e);
+                            ev.insert(&Entry::new(&key));
+                            let entities = ve.entry(v).or_default();
+                            if let Err(i) = entities.binary_search(&e) {
+                                entities.insert(i, e);
+                            }
+                        },
+                    );
+            });
+    }
+
+    pub fn attribute(&self) -> Id {
+        self.attribute
+    }
+
+    pub fn len(&self) -> usize {
+        self.ev.len() as usize
+    }
+
+    /// Returns all entities that have the given value for the indexed
+    /// attribute, in ascending order.
+    pub fn lookup(&self, value: &Value) -> &[Id] {
+        self.ve.get(value).map_or(&[], |entities| &entities[..])
+    }
+
+    pub fn pattern<'a, V>(
+        &'a self,
+        e: Variable<Id>,
+        v: Variable<V>,
+    ) -> AttributeIndexConstraint<'a, V>
+    where
+        V: Valuelike,
+    {
+        AttributeIndexConstraint::new(e, v, self)
+    }
+}
+
+pub struct AttributeIndexConstraint<'a, V>
+where
+    V: Valuelike,
+{
+    variable_e: Variable<Id>,
+    variable_v: Variable<V>,
+    index: &'a AttributeIndex,
+}
+
+impl<'a, V> AttributeIndexConstraint<'a, V>
+where
+    V: Valuelike,
+{
+    pub fn new(
+        variable_e: Variable<Id>,
+        variable_v: Variable<V>,
+        index: &'a AttributeIndex,
+    ) -> Self {
+        AttributeIndexConstraint {
+            variable_e,
+            variable_v,
+            index,
+        }
+    }
+}
+
+impl<'a, V> Constraint<'a> for AttributeIndexConstraint<'a, V>
+where
+    V: Valuelike,
+{
+    fn variables(&self) -> VariableSet {
+        let mut variables = VariableSet::new_empty();
+        variables.set(self.variable_e.index);
+        variables.set(self.variable_v.index);
+        variables
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.variable_e.index == variable || self.variable_v.index == variable
+    }
+
+    fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+        let e_var = self.variable_e.index == variable;
+        let v_var = self.variable_v.index == variable;
+
+        let e_bound = binding.get(self.variable_e.index).map(id_from_value);
+        let v_bound = binding.get(self.variable_v.index);
+
+        (match (e_bound, v_bound, e_var, v_var) {
+            (None, None, true, false) => self.index.ev.segmented_len(&[0; 0]),
+            (None, None, false, true) => self.index.ve.len() as u64,
+            (None, Some(v), true, false) => self.index.lookup(&v).len() as u64,
+            (Some(e), None, false, true) => self.index.ev.segmented_len(&e),
+            _ => panic!(),
+        }) as usize
+    }
+
+    fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+        let e_var = self.variable_e.index == variable;
+        let v_var = self.variable_v.index == variable;
+
+        let e_bound = binding.get(self.variable_e.index).map(id_from_value);
+        let v_bound = binding.get(self.variable_v.index);
+
+        let mut r = vec![];
+        match (e_bound, v_bound, e_var, v_var) {
+            (None, None, true, false) => self
+                .index
+                .ev
+                .infixes::<0, ID_LEN, _>(&[0; 0], &mut |e| r.push(id_into_value(e))),
+            (None, None, false, true) => r.extend(self.index.ve.keys().copied()),
+            (None, Some(v), true, false) => {
+                r.extend(self.index.lookup(&v).iter().copied().map(id_into_value))
+            }
+            (Some(e), None, false, true) => self
+                .index
+                .ev
+                .infixes::<ID_LEN, VALUE_LEN, _>(&e, &mut |v| r.push(v)),
+            _ => panic!(),
+        }
+        r
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+        let e_var = self.variable_e.index == variable;
+        let v_var = self.variable_v.index == variable;
+
+        let e_bound = binding.get(self.variable_e.index).map(id_from_value);
+        let v_bound = binding.get(self.variable_v.index);
+
+        match (e_bound, v_bound, e_var, v_var) {
+            (None, None, true, false) => {
+                proposals.retain(|value| self.index.ev.has_prefix(&id_from_value(*value)))
+            }
+            (None, None, false, true) => {
+                proposals.retain(|value| self.index.ve.contains_key(value))
+            }
+            (None, Some(v), true, false) => {
+                let entities = self.index.lookup(&v);
+                proposals.retain(|value| entities.binary_search(&id_from_value(*value)).is_ok())
+            }
+            (Some(e), None, false, true) => proposals.retain(|value| {
+                let mut prefix = [0u8; INDEX_KEY_LEN];
+                prefix[0..ID_LEN].copy_from_slice(&e);
+                prefix[ID_LEN..INDEX_KEY_LEN].copy_from_slice(value);
+                self.index.ev.has_prefix(&prefix)
+            }),
+            _ => panic!(),
+        }
+    }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "attribute index {} {} {}",
+            hex::encode_upper(self.index.attribute()),
+            self.variable_e,
+            self.variable_v
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::{find, types::ShortString, ufoid, NS};
+    use fake::{faker::name::raw::Name, locales::EN, Fake};
+
+    NS! {
+        pub namespace knights {
+            "8143F46E812E88C4544E7094080EC523" as loves: Id;
+            "D6E0F2A6E5214E1330565B4D4138E55C" as name: ShortString;
+        }
+    }
+
+    fn knights_set(n: usize) -> TribleSet {
+        let mut kb = TribleSet::new();
+        for _i in 0..n {
+            let lover_a = ufoid();
+            let lover_b = ufoid();
+            kb.union(knights::entity!(lover_a, {
+                name: (&Name(EN).fake::<String>()[..]).try_into().unwrap(),
+                loves: lover_b
+            }));
+            kb.union(knights::entity!(lover_b, {
+                name: (&Name(EN).fake::<String>()[..]).try_into().unwrap(),
+                loves: lover_a
+            }));
+        }
+        kb
+    }
+
+    #[test]
+    fn lookup_matches_pattern() {
+        let kb = knights_set(1000);
+        let index = AttributeIndex::build(&kb, knights::ids::name);
+        assert_eq!(index.len(), 2000);
+
+        for r in find!(ctx, (e, n), knights::pattern!(ctx, kb, [{e @ name: n}])) {
+            let (e, n): (Id, ShortString) = r.unwrap();
+            let mut expected: Vec<Id> = find!(
+                ctx,
+                (other),
+                knights::pattern!(ctx, kb, [{other @ name: (n.clone())}])
+            )
+            .map(|r| r.unwrap().0)
+            .collect();
+            let found = index.lookup(&Valuelike::into_value(&n));
+            expected.sort();
+            assert!(found.contains(&e));
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn constraint_matches_pattern() {
+        let kb = knights_set(1000);
+        let index = AttributeIndex::build(&kb, knights::ids::name);
+
+        let mut expected: Vec<(Id, Id)> = find!(
+            ctx,
+            (e, beloved, n),
+            knights::pattern!(ctx, kb, [{e @ name: n, loves: beloved}])
+        )
+        .map(|r| {
+            let (e, beloved, _n): (Id, Id, ShortString) = r.unwrap();
+            (e, beloved)
+        })
+        .collect();
+        expected.sort();
+
+        let mut found: Vec<(Id, Id)> = find!(
+            ctx,
+            (e, beloved, n),
+            crate::and!(
+                index.pattern(e, n),
+                knights::pattern!(ctx, kb, [{e @ loves: beloved}])
+            )
+        )
+        .map(|r| {
+            let (e, beloved, _n): (Id, Id, ShortString) = r.unwrap();
+            (e, beloved)
+        })
+        .collect();
+        found.sort();
+
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn update_equals_rebuild() {
+        let a = knights_set(500);
+        let b = knights_set(500);
+
+        let mut index = AttributeIndex::build(&a, knights::ids::name);
+        index.update(&b);
+        // Tribles that are already indexed are not added twice.
+        index.update(&b);
+
+        let mut all = a.clone();
+        all.union(b);
+        let rebuilt = AttributeIndex::build(&all, knights::ids::name);
+
+        assert_eq!(index.len(), rebuilt.len());
+        assert!(index.ve == rebuilt.ve);
+        assert!(index.ev == rebuilt.ev);
+    }
+}
@@ -59,6 +59,13 @@ impl<'a> Constraint<'a> for IntersectionConstraint<'a> {
             .iter()
             .for_each(|c| c.confirm(variable, binding, proposals));
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::with_children(
+            "and",
+            self.constraints.iter().map(|c| c.describe()).collect(),
+        )
+    }
 }
 
 #[macro_export]
@@ -31,6 +31,14 @@ impl<'a> Constraint<'a> for MaskConstraint<'a> {
     fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
         self.constraint.confirm(variable, binding, proposals)
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        let masked: Vec<String> = self.mask.into_iter().map(|v| format!("?{}", v)).collect();
+        ConstraintDescription::with_children(
+            format!("mask [{}]", masked.join(", ")),
+            vec![self.constraint.describe()],
+        )
+    }
 }
 
 #[macro_export]
@@ -0,0 +1,107 @@
+use super::*;
+
+/// Excludes the bindings for which a constraint has a solution.
+///
+/// Only the `shared` variables of the inner constraint are visible to the
+/// rest of the query, all other variables of the inner constraint are
+/// local to the negation. Once all shared variables are bound, the
+/// negation searches the local variables for a solution and rejects the
+/// binding if it finds one.
+///
+/// A negation can't propose values, so every shared variable has to be
+/// bound by some other constraint of the query.
+pub struct NegationConstraint<'a> {
+    shared: VariableSet,
+    constraint: Box<dyn Constraint<'a> + 'a>,
+}
+
+impl<'a> NegationConstraint<'a> {
+    pub fn new(shared: VariableSet, constraint: Box<dyn Constraint<'a> + 'a>) -> Self {
+        let shared = shared.intersect(constraint.variables());
+        NegationConstraint { shared, constraint }
+    }
+
+    /// Whether the inner constraint holds for a `binding` of all its
+    /// variables.
+    fn holds(&self, binding: &mut Binding) -> bool {
+        self.constraint.variables().into_iter().all(|variable| {
+            let value = binding.get(variable).unwrap();
+            let mut proposal = vec![value];
+            binding.unset(variable);
+            self.constraint.confirm(variable, binding, &mut proposal);
+            binding.set(variable, value);
+            !proposal.is_empty()
+        })
+    }
+
+    /// Whether the inner constraint has a solution that extends `binding`
+    /// with values for the `unbound` variables.
+    fn satisfiable(&self, binding: &mut Binding, unbound: &mut Vec<VariableId>) -> bool {
+        if unbound.is_empty() {
+            // The values were proposed by the inner constraint and thus
+            // confirmed by all of its parts.
+            return true;
+        }
+
+        let (index, &variable) = unbound
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &v)| self.constraint.estimate(v, binding))
+            .unwrap();
+        unbound.swap_remove(index);
+        let found = self
+            .constraint
+            .propose(variable, binding)
+            .into_iter()
+            .any(|value| {
+                binding.set(variable, value);
+                self.satisfiable(binding, &mut unbound.clone())
+            });
+        binding.unset(variable);
+        unbound.push(variable);
+        found
+    }
+}
+
+impl<'a> Constraint<'a> for NegationConstraint<'a> {
+    fn variables(&self) -> VariableSet {
+        self.shared
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.shared.is_set(variable)
+    }
+
+    fn estimate(&self, _variable: VariableId, _binding: &Binding) -> usize {
+        usize::MAX
+    }
+
+    fn propose(&self, _variable: VariableId, _binding: &Binding) -> Vec<Value> {
+        vec![]
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+        let mut pending = self.shared;
+        pending.unset(variable);
+        if !binding.bound.is_superset_of(&pending) {
+            // The proposals are checked when the last shared variable is bound.
+            return;
+        }
+
+        let mut binding = binding.clone();
+        let local = self.constraint.variables().subtract(self.shared);
+        proposals.retain(|&value| {
+            binding.set(variable, value);
+            if local.is_empty() {
+                !self.holds(&mut binding)
+            } else {
+                let mut unbound: Vec<VariableId> = local.into_iter().collect();
+                !self.satisfiable(&mut binding, &mut unbound)
+            }
+        });
+    }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::with_children("not", vec![self.constraint.describe()])
+    }
+}
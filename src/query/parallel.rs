@@ -0,0 +1,237 @@
+//! Running a query on several threads.
+//!
+//! Queries that scan large parts of a set, e.g. aggregations over all
+//! values of an attribute, spend most of their time below the first
+//! variable they bind. [execute_parallel] binds that variable once and
+//! splits its values between worker threads, which search their part of
+//! the results independently.
+//!
+//! Constraints are usually not [Sync], so every worker builds its own from
+//! a factory. Only the data the constraints are built from, e.g. a
+//! [crate::TribleSet], has to be shared between the threads. The [par_find]
+//! macro creates the factory from the same arguments as [crate::find].
+
+use std::thread;
+
+use rayon::prelude::*;
+
+use super::{Binding, Constraint, QueryError, VariableId};
+use crate::ValueParseError;
+
+/// Each worker gets this many parts of the values of the first variable
+/// on average, so that threads finishing early can take over work.
+const PARTS_PER_THREAD: usize = 4;
+
+/// Returns all results of the constraints built by `constraint`, in no
+/// particular order.
+///
+/// `constraint` must build the same constraint every time it is called,
+/// i.e. with the same variables.
+pub fn execute_parallel<'a, F, C, P, R>(
+    constraint: F,
+    postprocessing: P,
+) -> Vec<Result<R, QueryError>>
+where
+    F: Fn() -> C + Sync,
+    C: Constraint<'a>,
+    P: Fn(&Binding) -> Result<R, ValueParseError> + Sync,
+    R: Send,
+{
+    let root = constraint();
+    let mut unbound: Vec<VariableId> = root.variables().into_iter().collect();
+    let binding = Binding::default();
+    let (index, &variable) = match unbound
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &v)| root.estimate(v, &binding))
+    {
+        Some(first) => first,
+        None => return vec![postprocessing(&binding).map_err(QueryError::from)],
+    };
+    unbound.swap_remove(index);
+    let values = root.propose(variable, &binding);
+    let failures = binding.take_failures();
+    drop(root);
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let part_len = std::cmp::max(1, values.len() / (threads * PARTS_PER_THREAD));
+    let parts: Vec<_> = values.chunks(part_len).collect();
+    let results: Vec<Vec<Result<R, QueryError>>> = parts
+        .into_par_iter()
+        .map(|part| {
+            let constraint = constraint();
+            let mut binding = Binding::default();
+            let mut unbound = unbound.clone();
+            let mut results = Vec::new();
+            for &value in part {
+                binding.set(variable, value);
+                search(
+                    &constraint,
+                    &postprocessing,
+                    &mut binding,
+                    &mut unbound,
+                    &mut results,
+                );
+            }
+            results
+        })
+        .collect();
+    failures
+        .into_iter()
+        .map(Err)
+        .chain(results.into_iter().flatten())
+        .collect()
+}
+
+/// Depth first search over the `unbound` variables, like [super::Query].
+fn search<'a, C, P, R>(
+    constraint: &C,
+    postprocessing: &P,
+    binding: &mut Binding,
+    unbound: &mut Vec<VariableId>,
+    results: &mut Vec<Result<R, QueryError>>,
+) where
+    C: Constraint<'a>,
+    P: Fn(&Binding) -> Result<R, ValueParseError>,
+{
+    let (index, &variable) = match unbound
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &v)| constraint.estimate(v, binding))
+    {
+        Some(next) => next,
+        None => {
+            results.push(postprocessing(binding).map_err(QueryError::from));
+            return;
+        }
+    };
+    unbound.swap_remove(index);
+    let values = constraint.propose(variable, binding);
+    results.extend(binding.take_failures().into_iter().map(Err));
+    for value in values {
+        binding.set(variable, value);
+        search(constraint, postprocessing, binding, unbound, results);
+    }
+    binding.unset(variable);
+    unbound.push(variable);
+}
+
+/// Like [crate::find], but runs the query on several threads with
+/// [execute_parallel] and returns a `Vec` of the results in no particular
+/// order.
+///
+/// The constraint expression is evaluated once per worker thread.
+#[macro_export]
+macro_rules! par_find {
+    ($ctx:ident, ($($Var:ident),+), $Constraint:expr) => {
+        {
+            let mut $ctx = $crate::query::VariableContext::new();
+            $(let $Var = $ctx.next_variable();)*
+            let next_index = $ctx.next_index;
+            $crate::query::parallel::execute_parallel(
+                || {
+                    let mut $ctx = $crate::query::VariableContext { next_index };
+                    $Constraint
+                },
+                move |binding| {
+                    Ok(($($Var.extract(binding)?),+,))
+                },
+            )
+        }
+    };
+}
+
+pub use par_find;
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::query::find;
+    use crate::{types::ShortString, ufoid, Id, TribleSet, NS};
+
+    use super::*;
+
+    NS! {
+        pub namespace knights {
+            "5C1B3E6D2A4F8E7C9B0A1D2E3F4C5B6A" as loves: Id;
+            "6D2C4F7E3B5A9F8D0C1B2E3F4A5D6C7B" as name: ShortString;
+        }
+    }
+
+    fn kingdom() -> TribleSet {
+        let mut kb = TribleSet::new();
+        for i in 0..500 {
+            let (a, b) = (ufoid(), ufoid());
+            kb.union(knights::entity!(a, {
+                name: (&format!("Lover {}", i % 7)[..]).try_into().unwrap(),
+                loves: b
+            }));
+            kb.union(knights::entity!(b, {
+                name: (&format!("Beloved {}", i % 11)[..]).try_into().unwrap(),
+                loves: a
+            }));
+        }
+        kb
+    }
+
+    fn sorted<T: Ord>(mut rows: Vec<T>) -> Vec<T> {
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn matches_sequential() {
+        let kb = kingdom();
+        type Row = (Id, Id, ShortString);
+
+        let unwrap = |r: Result<Row, QueryError>| {
+            let (e, f, n) = r.unwrap();
+            (e, f, String::from(<&str>::from(&n)))
+        };
+        let sequential: Vec<_> = find!(
+            ctx,
+            (lover, beloved, name),
+            knights::pattern!(ctx, kb, [{lover @ loves: beloved}, {beloved @ name: name}])
+        )
+        .map(unwrap)
+        .collect();
+        let parallel: Vec<_> = par_find!(
+            ctx,
+            (lover, beloved, name),
+            knights::pattern!(ctx, kb, [{lover @ loves: beloved}, {beloved @ name: name}])
+        )
+        .into_iter()
+        .map(unwrap)
+        .collect();
+        assert_eq!(sequential.len(), 1000);
+        assert_eq!(sorted(parallel), sorted(sequential));
+    }
+
+    #[test]
+    fn bound_values() {
+        let kb = kingdom();
+        let count = |name: &str| {
+            let name: ShortString = name.try_into().unwrap();
+            let sequential = find!(
+                ctx,
+                (lover, beloved),
+                knights::pattern!(ctx, kb, [
+                    {lover @ name: (name.clone()), loves: beloved}
+                ])
+            )
+            .count();
+            let parallel: Vec<Result<(Id, Id), QueryError>> = par_find!(
+                ctx,
+                (lover, beloved),
+                knights::pattern!(ctx, kb, [
+                    {lover @ name: (name.clone()), loves: beloved}
+                ])
+            );
+            assert_eq!(parallel.len(), sequential);
+            parallel.len()
+        };
+        assert_eq!(count("Lover 3"), 71);
+        assert_eq!(count("Nobody"), 0);
+    }
+}
@@ -3,7 +3,10 @@ use crate::{
     Value, Valuelike, VALUE_LEN,
 };
 
-use super::{Binding, ContainsConstraint, Constraint, Variable, VariableId, VariableSet};
+use super::{
+    Binding, Constraint, ConstraintDescription, ContainsConstraint, Variable, VariableId,
+    VariableSet,
+};
 
 pub struct PatchConstraint<'a, T> {
     variable: Variable<T>,
@@ -48,6 +51,14 @@ where
     fn confirm(&self, _variable: VariableId, _binding: &Binding, proposals: &mut Vec<Value>) {
         proposals.retain(|v| self.patch.has_prefix(v));
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "{} in patch ({} values)",
+            self.variable,
+            self.patch.len()
+        ))
+    }
 }
 
 impl<'a, T> ContainsConstraint<'a, T> for PATCH<VALUE_LEN, IdentityOrder, SingleSegmentation>
@@ -0,0 +1,173 @@
+//! Building patterns at runtime.
+//!
+//! [PatternBuilder] creates the same constraints as a namespace's
+//! `pattern!` macro, but takes attribute ids as values, so the attributes
+//! of a query can come from user input or configuration. Variables are
+//! allocated from the [VariableContext] of the query, e.g. the one
+//! declared by [crate::find].
+//!
+//! ```
+//! use std::convert::TryInto;
+//! use tribles::query::PatternBuilder;
+//! use tribles::{find, types::ShortString, ufoid, Id, NS};
+//!
+//! NS! {
+//!     pub namespace knights {
+//!         "6A1C3E5B7D9F0A2C4E6B8D0F1A3C5E7B" as loves: tribles::Id;
+//!         "7B2D4F6C8E0A1B3D5F7C9E1A2B4D6F8C" as name: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! let romeo = ufoid();
+//! let juliet = ufoid();
+//! let mut kb = knights::entity!(romeo, { name: "Romeo".try_into().unwrap(), loves: juliet });
+//! kb.union(knights::entity!(juliet, { name: "Juliet".try_into().unwrap(), loves: romeo }));
+//!
+//! // E.g. looked up by name in a configuration.
+//! let (loves, name) = (knights::ids::loves, knights::ids::name);
+//!
+//! let r: Vec<_> = find!(ctx, (beloved, beloved_name), {
+//!     let mut pattern = PatternBuilder::new(&mut ctx, &kb);
+//!     let lover = pattern.entity_id(romeo);
+//!     pattern.attr_var(lover, loves, beloved);
+//!     pattern.attr_var(beloved, name, beloved_name);
+//!     pattern.build()
+//! })
+//! .collect();
+//!
+//! let expected: ShortString = "Juliet".try_into().unwrap();
+//! assert_eq!(r, vec![Ok((juliet, expected))]);
+//! ```
+
+use super::{Constraint, IntersectionConstraint, TriblePattern, Variable, VariableContext};
+use crate::{Id, Valuelike};
+
+pub struct PatternBuilder<'a, 'c, T> {
+    ctx: &'c mut VariableContext,
+    set: &'a T,
+    constraints: Vec<Box<dyn Constraint<'a> + 'a>>,
+}
+
+impl<'a, 'c, T> PatternBuilder<'a, 'c, T>
+where
+    T: TriblePattern,
+{
+    pub fn new(ctx: &'c mut VariableContext, set: &'a T) -> Self {
+        PatternBuilder {
+            ctx,
+            set,
+            constraints: Vec::new(),
+        }
+    }
+
+    /// A new entity variable, like an entity without an id in `pattern!`.
+    pub fn entity(&mut self) -> Variable<Id> {
+        self.ctx.next_variable()
+    }
+
+    /// A new entity variable bound to `id`, like `{(id) @ ...}` in
+    /// `pattern!`.
+    pub fn entity_id(&mut self, id: Id) -> Variable<Id> {
+        let e: Variable<Id> = self.ctx.next_variable();
+        self.constraints.push(Box::new(e.is(id)));
+        e
+    }
+
+    /// Matches the values of `attribute` of `entity` with `value`, like
+    /// `attribute: value` in `pattern!`.
+    pub fn attr_var<V>(
+        &mut self,
+        entity: Variable<Id>,
+        attribute: Id,
+        value: Variable<V>,
+    ) -> &mut Self
+    where
+        V: Valuelike + 'a,
+        T::PatternConstraint<'a, V>: 'a,
+    {
+        let a: Variable<Id> = self.ctx.next_variable();
+        self.constraints.push(Box::new(a.is(attribute)));
+        self.constraints
+            .push(Box::new(self.set.pattern(entity, a, value)));
+        self
+    }
+
+    /// Requires `entity` to have `value` for `attribute`, like
+    /// `attribute: (value)` in `pattern!`.
+    pub fn attr_lit<V>(&mut self, entity: Variable<Id>, attribute: Id, value: V) -> &mut Self
+    where
+        V: Valuelike + 'a,
+        T::PatternConstraint<'a, V>: 'a,
+    {
+        let v: Variable<V> = self.ctx.next_variable();
+        self.constraints.push(Box::new(v.is(value)));
+        self.attr_var(entity, attribute, v)
+    }
+
+    pub fn build(self) -> IntersectionConstraint<'a> {
+        IntersectionConstraint::new(self.constraints)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::query::QueryError;
+    use crate::{find, types::ShortString, ufoid, TribleSet, NS};
+
+    NS! {
+        pub namespace knights {
+            "4F8A2C6E0B3D5F7A9C1E3B5D7F9A1C3E" as loves: Id;
+            "5A9B3D7F1C4E6A8B0D2F4C6E8A0B2D4F" as name: ShortString;
+            "6B0C4E8A2D5F7B9C1E3A5D7F9B1C3E5A" as title: ShortString;
+        }
+    }
+
+    #[test]
+    fn matches_pattern_macro() {
+        let mut kb = TribleSet::new();
+        for i in 0..20 {
+            let (a, b) = (ufoid(), ufoid());
+            kb.union(knights::entity!(a, {
+                name: (&format!("Lover {}", i)[..]).try_into().unwrap(),
+                title: (if i % 2 == 0 { "Prince" } else { "Knight" }).try_into().unwrap(),
+                loves: b
+            }));
+            kb.union(knights::entity!(b, {
+                name: (&format!("Beloved {}", i)[..]).try_into().unwrap(),
+                loves: a
+            }));
+        }
+
+        type Row = (Id, Id, ShortString, ShortString);
+        let mut expected: Vec<Row> = find!(
+            ctx,
+            (lover, beloved, lover_name, beloved_name),
+            knights::pattern!(ctx, kb, [
+                {lover @ name: lover_name, title: ("Prince".try_into().unwrap()), loves: beloved},
+                {beloved @ name: beloved_name}
+            ])
+        )
+        .map(|r: Result<Row, QueryError>| r.unwrap())
+        .collect();
+        let mut built: Vec<Row> = find!(ctx, (lover, beloved, lover_name, beloved_name), {
+            let mut pattern = PatternBuilder::new(&mut ctx, &kb);
+            let prince: ShortString = "Prince".try_into().unwrap();
+            pattern
+                .attr_var(lover, knights::ids::name, lover_name)
+                .attr_lit(lover, knights::ids::title, prince)
+                .attr_var(lover, knights::ids::loves, beloved)
+                .attr_var(beloved, knights::ids::name, beloved_name);
+            pattern.build()
+        })
+        .map(|r: Result<Row, QueryError>| r.unwrap())
+        .collect();
+        expected.sort_by_key(|r| r.0);
+        built.sort_by_key(|r| r.0);
+
+        assert_eq!(expected.len(), 10);
+        assert_eq!(built, expected);
+    }
+}
@@ -0,0 +1,371 @@
+//! Approximate query answers from a random subset of a variable's values.
+//!
+//! [SampleConstraint] wraps another constraint and only lets a
+//! pseudo-random fraction of the values of one variable through.
+//! Whether a value is kept only depends on the value and the seed, so the
+//! same subset is used no matter in which order the engine binds the
+//! variables, and the sampled query does a proportional fraction of the
+//! work of the full one.
+//!
+//! [Query::sample] and [estimate_count] instead shuffle the values of the
+//! first variable by their hashes and only search below as many of them as
+//! they need, i.e. until `k` results are found or `sample_size` values are
+//! visited.
+//!
+//! For random ids, like the ones produced by [crate::ufoid], the values
+//! are sampled uniformly. The results are not, when few values of the
+//! sampled variable account for most of the rows: the rows of one value are
+//! returned together, and the confidence interval of [estimate_count] gets
+//! wide, or too narrow if the sample misses the few large values entirely.
+
+use core::hash::Hasher;
+use std::collections::HashMap;
+
+use siphasher::sip::SipHasher24;
+
+use super::{
+    Binding, Constraint, ConstraintDescription, Query, QueryError, Search, State, VariableId,
+    VariableSet,
+};
+use crate::{Value, ValueParseError};
+
+pub struct SampleConstraint<C> {
+    constraint: C,
+    variable: VariableId,
+    threshold: u64,
+    seed: u64,
+}
+
+impl<'a, C> SampleConstraint<C>
+where
+    C: Constraint<'a>,
+{
+    /// Keeps each value of `variable` with probability `rate`.
+    pub fn new(constraint: C, variable: VariableId, rate: f64, seed: u64) -> Self {
+        assert!((0.0..=1.0).contains(&rate));
+        SampleConstraint {
+            constraint,
+            variable,
+            threshold: (rate * u64::MAX as f64) as u64,
+            seed,
+        }
+    }
+
+    fn keep(&self, value: &Value) -> bool {
+        value_hash(value, self.seed) <= self.threshold
+    }
+}
+
+fn value_hash(value: &Value, seed: u64) -> u64 {
+    let mut hasher = SipHasher24::new_with_keys(seed, 0);
+    hasher.write(&value[..]);
+    hasher.finish()
+}
+
+impl<'a, C> Constraint<'a> for SampleConstraint<C>
+where
+    C: Constraint<'a>,
+{
+    fn variables(&self) -> VariableSet {
+        self.constraint.variables()
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.constraint.variable(variable)
+    }
+
+    fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+        self.constraint.estimate(variable, binding)
+    }
+
+    fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+        let mut proposals = self.constraint.propose(variable, binding);
+        if variable == self.variable {
+            proposals.retain(|v| self.keep(v));
+        }
+        proposals
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+        if variable == self.variable {
+            proposals.retain(|v| self.keep(v));
+        }
+        self.constraint.confirm(variable, binding, proposals);
+    }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::with_children(
+            format!(
+                "sample ?{} at {:.3} (seed {})",
+                self.variable,
+                self.threshold as f64 / u64::MAX as f64,
+                self.seed
+            ),
+            vec![self.constraint.describe()],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CountEstimate {
+    pub estimate: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+}
+
+/// Estimates the number of results of `constraint` by counting the results
+/// for a sample of `sample_size` values of `variable` and extrapolating.
+///
+/// The sampled values are the ones with the smallest hashes among all
+/// values the constraint proposes for `variable` when nothing is bound, and
+/// only the results below them are searched. The returned interval is an
+/// approximate 95% confidence interval for drawing `sample_size` of the
+/// values without replacement, based on the variance of the number of
+/// results per sampled value. For constraints with at most one result per
+/// value it is the normal approximation of the hypergeometric distribution.
+pub fn estimate_count<'a, C>(
+    constraint: C,
+    variable: VariableId,
+    sample_size: usize,
+    seed: u64,
+) -> CountEstimate
+where
+    C: Constraint<'a>,
+{
+    assert!(sample_size > 1);
+    let mut query = Query::new(constraint, |_| Ok(()));
+    let population = query.start_sampled(variable, seed);
+    if population == 0 {
+        return CountEstimate {
+            estimate: 0.0,
+            ci_low: 0.0,
+            ci_high: 0.0,
+        };
+    }
+    let sample_size = std::cmp::min(sample_size, population);
+    let state = query.stack.last_mut().unwrap();
+    let sampled: Vec<Value> = state.values.by_ref().take(sample_size).collect();
+    state.values = sampled.into_iter();
+
+    let mut counts: HashMap<Value, usize> = HashMap::new();
+    while let Some(row) = query.next() {
+        if row.is_ok() {
+            *counts
+                .entry(query.binding.get(variable).unwrap())
+                .or_default() += 1;
+        }
+    }
+
+    let (n, population) = (sample_size as f64, population as f64);
+    let hits = counts.values().sum::<usize>() as f64;
+    let mean = hits / n;
+    // Sampled values without results contribute a squared deviation of
+    // `mean * mean` each.
+    let deviations: f64 = counts
+        .values()
+        .map(|&c| (c as f64 - mean).powi(2))
+        .sum::<f64>()
+        + (n - counts.len() as f64) * mean * mean;
+    let variance = if n > 1.0 { deviations / (n - 1.0) } else { 0.0 };
+    let finite_population = 1.0 - n / population;
+    let margin = 1.96 * population * (finite_population * variance / n).sqrt();
+    let estimate = population * mean;
+    CountEstimate {
+        estimate,
+        ci_low: (estimate - margin).max(hits),
+        ci_high: estimate + margin,
+    }
+}
+
+impl<'a, C: Constraint<'a>, P: Fn(&Binding) -> Result<R, ValueParseError>, R> Query<C, P, R> {
+    /// Returns `k` pseudo-randomly chosen results, or all results if there
+    /// are at most `k` of them.
+    ///
+    /// The values of the variable with the fewest candidates, ignoring
+    /// variables with a single candidate, are shuffled by their hashes with
+    /// `seed` and bound first. The search stops once `k` results are found
+    /// below the first values, so only the sampled branches are explored.
+    /// The same seed always selects the same rows.
+    pub fn sample(mut self, k: usize, seed: u64) -> Vec<Result<R, QueryError>> {
+        if k > 0 {
+            // Variables with a single candidate, e.g. constant attributes,
+            // are bound first by the query, but there is nothing to shuffle.
+            let variable = self
+                .unbound
+                .iter()
+                .map(|&v| (self.constraint.estimate(v, &self.binding), v))
+                .filter(|&(estimate, _)| estimate > 1)
+                .min();
+            if let Some((_, variable)) = variable {
+                self.start_sampled(variable, seed);
+            }
+        }
+        self.take_limited(k).collect()
+    }
+
+    /// Proposes the values of `variable` and pushes them onto the stack in
+    /// the order of their hashes with `seed`, so that the value with the
+    /// smallest hash is bound first. Returns the number of values.
+    fn start_sampled(&mut self, variable: VariableId, seed: u64) -> usize {
+        assert!(
+            self.stack.is_empty() && self.order.is_empty(),
+            "sampled queries must not be ordered or already running"
+        );
+        self.unbound.retain(|&v| v != variable);
+        let mut values = self.constraint.propose(variable, &self.binding);
+        self.failures.extend(self.binding.take_failures());
+        values.sort_by_cached_key(|v| value_hash(v, seed));
+        let len = values.len();
+        self.stack.push(State {
+            variable,
+            values: values.into_iter(),
+        });
+        self.mode = Search::Horizontal;
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::{and, compute, find, types::ShortString, ufoid, Id, TribleSet, NS};
+
+    NS! {
+        pub namespace knights {
+            "1D5A7E2F3C4B5A6978879695A4B3C2D1" as name: ShortString;
+            "2E6B8F3A4D5C6B7A89988A7B6C5D4E3F" as serves: Id;
+            "3F7C9A4B5E6D7C8B9AA99B8C7D6E5F40" as title: ShortString;
+        }
+    }
+
+    fn knights(n: usize) -> TribleSet {
+        let mut kb = TribleSet::new();
+        for i in 0..n {
+            kb.union(knights::entity!(ufoid(), {
+                name: (&format!("Knight {}", i)[..]).try_into().unwrap()
+            }));
+        }
+        kb
+    }
+
+    #[test]
+    fn estimate_within_interval() {
+        // Every knight serves a lord, but only 3 in 10 lords are titled.
+        // The attribute isn't bound when the knights are proposed, so the
+        // titled lords are candidates too, and 3000 of the 13000 sampled
+        // entities have a result.
+        let mut kb = TribleSet::new();
+        for i in 0..10000 {
+            let lord = ufoid();
+            kb.union(knights::entity!(ufoid(), { serves: lord }));
+            if i % 10 < 3 {
+                kb.union(knights::entity!(lord, { title: "Sir".try_into().unwrap() }));
+            }
+        }
+
+        let mut within = 0;
+        for seed in 0..20 {
+            let mut ctx = crate::query::VariableContext::new();
+            let knight: crate::query::Variable<Id> = ctx.next_variable();
+            let lord: crate::query::Variable<Id> = ctx.next_variable();
+            let title: crate::query::Variable<ShortString> = ctx.next_variable();
+            let constraint =
+                knights::pattern!(ctx, kb, [{knight @ serves: lord}, {lord @ title: title}]);
+            let estimate = estimate_count(constraint, knight.index, 500, seed);
+            // The hypergeometric standard deviation of the hits is about
+            // sqrt(500 * 0.23 * 0.77 * 0.96) = 9.2, i.e. 240 extrapolated.
+            let width = estimate.ci_high - estimate.ci_low;
+            assert!(800.0 < width && width < 1100.0, "{:?}", estimate);
+            if estimate.ci_low <= 3000.0 && 3000.0 <= estimate.ci_high {
+                within += 1;
+            }
+        }
+        assert!(within >= 15);
+    }
+
+    #[test]
+    fn sample_explores_sampled_branches() {
+        let kb = knights(10000);
+        let computed = Cell::new(0);
+        let counter = &computed;
+
+        let sample = find!(
+            ctx,
+            (e, n, upper),
+            and!(
+                knights::pattern!(ctx, kb, [{e @ name: n}]),
+                compute!(upper = |n: ShortString| {
+                    counter.set(counter.get() + 1);
+                    let n: &str = (&n).into();
+                    ShortString::new(n.to_uppercase()).unwrap()
+                }, from n)
+            )
+        )
+        .sample(10, 7);
+        assert_eq!(sample.len(), 10);
+        assert_eq!(computed.get(), 10);
+    }
+
+    #[test]
+    fn sample_rows() {
+        let kb = knights(1000);
+
+        let rows: Vec<_> = find!(
+            ctx,
+            (e, n),
+            SampleConstraint::new(knights::pattern!(ctx, kb, [{e @ name: n}]), e.index, 0.2, 7)
+        )
+        .take(10)
+        .collect();
+        assert_eq!(rows.len(), 10);
+
+        let all: Vec<_> = find!(
+            ctx,
+            (e, n),
+            SampleConstraint::new(knights::pattern!(ctx, kb, [{e @ name: n}]), e.index, 0.2, 7)
+        )
+        .collect();
+        let again: Vec<_> = find!(
+            ctx,
+            (e, n),
+            SampleConstraint::new(knights::pattern!(ctx, kb, [{e @ name: n}]), e.index, 0.2, 7)
+        )
+        .collect();
+        assert!(all.len() > 100 && all.len() < 300);
+        assert_eq!(all, again);
+    }
+
+    #[test]
+    fn sample_exactly_k() {
+        let kb = knights(1000);
+
+        let sample = find!(ctx, (e, n), knights::pattern!(ctx, kb, [{e @ name: n}])).sample(10, 7);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.iter().all(|row| row.is_ok()));
+
+        let again = find!(ctx, (e, n), knights::pattern!(ctx, kb, [{e @ name: n}])).sample(10, 7);
+        assert_eq!(sample, again);
+
+        let other = find!(ctx, (e, n), knights::pattern!(ctx, kb, [{e @ name: n}])).sample(10, 8);
+        assert_ne!(sample, other);
+
+        let small = knights(5);
+        let all = find!(ctx, (e, n), knights::pattern!(ctx, small, [{e @ name: n}])).sample(10, 7);
+        assert_eq!(all.len(), 5);
+    }
+
+    #[test]
+    fn exact_count_for_small_sets() {
+        let kb = knights(50);
+        let mut ctx = crate::query::VariableContext::new();
+        let e: crate::query::Variable<Id> = ctx.next_variable();
+        let n: crate::query::Variable<ShortString> = ctx.next_variable();
+        let constraint = knights::pattern!(ctx, kb, [{e @ name: n}]);
+        let estimate = estimate_count(constraint, e.index, 100, 1);
+        assert_eq!(estimate.estimate, 50.0);
+        assert_eq!((estimate.ci_low, estimate.ci_high), (50.0, 50.0));
+    }
+}
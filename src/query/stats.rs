@@ -0,0 +1,710 @@
+//! Per-attribute statistics over the values of a [TribleSet].
+//!
+//! The query engine doesn't need these for a [TribleSet], its PATCH
+//! indices provide exact counts for every bound prefix. They are useful for
+//! planning work outside of a single query, e.g. to spot heavily skewed
+//! attributes or to estimate the selectivity of a value without touching
+//! the dataset, and can be stored as a blob next to the data they describe.
+//!
+//! Queries over sources that are costly to count in can take their
+//! estimates from the statistics instead, by wrapping the source with
+//! [SetStatistics::over]:
+//!
+//! ```
+//! use std::convert::TryInto;
+//! use tribles::query::stats::SetStatistics;
+//! use tribles::{find, ufoid, TribleSet, NS};
+//!
+//! NS! {
+//!     pub namespace books {
+//!         "A4C09E1C33F0E0F3A2B9C7D6E5F40112" as genre: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! let mut set = TribleSet::new();
+//! for genre in ["fantasy", "fantasy", "poetry"] {
+//!     set.union(books::entity!(ufoid(), { genre: genre.try_into().unwrap() }));
+//! }
+//! let stats = SetStatistics::analyze(&set, None);
+//! let analyzed = stats.over(&set);
+//!
+//! let poetry = find!(
+//!     ctx,
+//!     (book),
+//!     books::pattern!(ctx, analyzed, [{book @ genre: ("poetry".try_into().unwrap())}])
+//! );
+//! assert_eq!(poetry.count(), 1);
+//! ```
+//!
+//! [TribleSet::attribute_stats] and [TribleSet::attributes] provide the
+//! basic counts without a histogram, directly from the indices.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+use anybytes::Bytes;
+use digest::{typenum::U32, Digest};
+
+use crate::patch::PATCH;
+use crate::query::{Binding, Constraint, TriblePattern, Variable, VariableId, VariableSet};
+use crate::trible::{AVEOrder, TribleSegmentation};
+use crate::types::Hash;
+use crate::{
+    id_from_value, BlobParseError, Bloblike, Handle, Id, TribleSet, Value, Valuelike, ID_LEN,
+    VALUE_LEN,
+};
+
+/// The default number of buckets of an attribute histogram.
+pub const HISTOGRAM_BUCKETS: usize = 16;
+
+/// A bucket of an equi-depth histogram, covering all values up to and
+/// including `upper` that are larger than the previous bucket's bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bucket {
+    pub upper: Value,
+    /// The number of distinct values in this bucket.
+    pub values: u64,
+    /// The number of tribles with a value in this bucket.
+    pub tribles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeStatistics {
+    pub entities: u64,
+    pub values: u64,
+    pub tribles: u64,
+    pub histogram: Vec<Bucket>,
+}
+
+impl AttributeStatistics {
+    /// Estimates the number of tribles with the given value, assuming that
+    /// the values within a histogram bucket are uniformly distributed.
+    pub fn estimate(&self, value: &Value) -> u64 {
+        match self.histogram.iter().find(|b| value <= &b.upper) {
+            Some(bucket) => (bucket.tribles + bucket.values - 1) / bucket.values,
+            None => 0,
+        }
+    }
+}
+
+type AVEIndex = PATCH<64, AVEOrder, TribleSegmentation>;
+
+/// A step of the histogram construction for the value prefix of an
+/// attribute in the AVE index, see [step_at].
+enum Step {
+    /// All values continue with this byte.
+    Descend(u8),
+    /// The values grouped by the following bytes, one bucket per prefix
+    /// bounded by the largest value with that prefix.
+    Counts(Vec<Bucket>),
+}
+
+/// Looks at the values continuing the first `START` bytes of `prefix`,
+/// where `END` is `START + LEN`.
+///
+/// Only the distinct infixes of up to `LEN` bytes are visited and counted
+/// with [PATCH::count_prefix] and [PATCH::segmented_len], so the work is
+/// bounded by the number of prefixes regardless of the number of values.
+fn step<const START: usize, const LEN: usize, const END: usize>(
+    ave: &AVEIndex,
+    prefix: &[u8; ID_LEN + VALUE_LEN],
+) -> Step {
+    let start: [u8; START] = prefix[0..START].try_into().unwrap();
+    if END < ID_LEN + VALUE_LEN {
+        let mut next = vec![];
+        ave.infixes::<START, 1, _>(&start, |byte: [u8; 1]| next.push(byte[0]));
+        if let [byte] = next[..] {
+            return Step::Descend(byte);
+        }
+    }
+
+    let mut counts = vec![];
+    ave.infixes::<START, LEN, _>(&start, |infix: [u8; LEN]| {
+        let mut key = [0u8; END];
+        key[0..START].copy_from_slice(&start);
+        key[START..END].copy_from_slice(&infix);
+        let mut upper = [u8::MAX; VALUE_LEN];
+        upper[0..END - ID_LEN].copy_from_slice(&key[ID_LEN..END]);
+        counts.push(Bucket {
+            upper,
+            values: ave.segmented_len(&key),
+            tribles: ave.count_prefix(&key),
+        });
+    });
+    Step::Counts(counts)
+}
+
+/// Calls [step] for the value prefix of `depth` bytes with a two byte
+/// window, which is cut short at the end of the value.
+fn step_at(ave: &AVEIndex, prefix: &[u8; ID_LEN + VALUE_LEN], depth: usize) -> Step {
+    match depth {
+        16 => step::<16, 2, 18>(ave, prefix),
+        17 => step::<17, 2, 19>(ave, prefix),
+        18 => step::<18, 2, 20>(ave, prefix),
+        19 => step::<19, 2, 21>(ave, prefix),
+        20 => step::<20, 2, 22>(ave, prefix),
+        21 => step::<21, 2, 23>(ave, prefix),
+        22 => step::<22, 2, 24>(ave, prefix),
+        23 => step::<23, 2, 25>(ave, prefix),
+        24 => step::<24, 2, 26>(ave, prefix),
+        25 => step::<25, 2, 27>(ave, prefix),
+        26 => step::<26, 2, 28>(ave, prefix),
+        27 => step::<27, 2, 29>(ave, prefix),
+        28 => step::<28, 2, 30>(ave, prefix),
+        29 => step::<29, 2, 31>(ave, prefix),
+        30 => step::<30, 2, 32>(ave, prefix),
+        31 => step::<31, 2, 33>(ave, prefix),
+        32 => step::<32, 2, 34>(ave, prefix),
+        33 => step::<33, 2, 35>(ave, prefix),
+        34 => step::<34, 2, 36>(ave, prefix),
+        35 => step::<35, 2, 37>(ave, prefix),
+        36 => step::<36, 2, 38>(ave, prefix),
+        37 => step::<37, 2, 39>(ave, prefix),
+        38 => step::<38, 2, 40>(ave, prefix),
+        39 => step::<39, 2, 41>(ave, prefix),
+        40 => step::<40, 2, 42>(ave, prefix),
+        41 => step::<41, 2, 43>(ave, prefix),
+        42 => step::<42, 2, 44>(ave, prefix),
+        43 => step::<43, 2, 45>(ave, prefix),
+        44 => step::<44, 2, 46>(ave, prefix),
+        45 => step::<45, 2, 47>(ave, prefix),
+        46 => step::<46, 2, 48>(ave, prefix),
+        47 => step::<47, 1, 48>(ave, prefix),
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetStatistics {
+    pub attributes: BTreeMap<Id, AttributeStatistics>,
+}
+
+impl SetStatistics {
+    /// Computes the statistics for the given attributes, or for all
+    /// attributes in the set when `attrs` is `None`.
+    ///
+    /// The histogram of an attribute groups its values by the first two
+    /// bytes in which they differ, and is built from the prefix counts of
+    /// the indices. The work per attribute is bounded by the number of
+    /// such prefixes, not by the number of values.
+    pub fn analyze(set: &TribleSet, attrs: Option<&[Id]>) -> SetStatistics {
+        SetStatistics::analyze_with_buckets(set, attrs, HISTOGRAM_BUCKETS)
+    }
+
+    pub fn analyze_with_buckets(
+        set: &TribleSet,
+        attrs: Option<&[Id]>,
+        buckets: usize,
+    ) -> SetStatistics {
+        assert!(buckets > 0);
+        let attrs: Vec<Id> = match attrs {
+            Some(attrs) => attrs.to_vec(),
+            None => set.attributes().map(|(a, _)| a).collect(),
+        };
+
+        let set = set.indices();
+        let mut statistics = SetStatistics::default();
+        for a in attrs {
+            if !set.aev.has_prefix(&a) {
+                continue;
+            }
+
+            // Skip the value bytes that all values share, the histogram is
+            // built from the two bytes where they start to differ.
+            let mut prefix = [0u8; ID_LEN + VALUE_LEN];
+            prefix[0..ID_LEN].copy_from_slice(&a);
+            let mut depth = ID_LEN;
+            let prefixes = loop {
+                match step_at(&set.ave, &prefix, depth) {
+                    Step::Descend(byte) => {
+                        prefix[depth] = byte;
+                        depth += 1;
+                    }
+                    Step::Counts(prefixes) => break prefixes,
+                }
+            };
+            let tribles: u64 = prefixes.iter().map(|b| b.tribles).sum();
+            let depth = (tribles + buckets as u64 - 1) / buckets as u64;
+
+            let mut histogram = vec![];
+            let mut bucket: Option<Bucket> = None;
+            for prefix in &prefixes {
+                let b = bucket.get_or_insert(Bucket {
+                    upper: prefix.upper,
+                    values: 0,
+                    tribles: 0,
+                });
+                b.upper = prefix.upper;
+                b.values += prefix.values;
+                b.tribles += prefix.tribles;
+                if b.tribles >= depth {
+                    histogram.extend(bucket.take());
+                }
+            }
+            histogram.extend(bucket);
+
+            statistics.attributes.insert(
+                a,
+                AttributeStatistics {
+                    entities: set.aev.segmented_len(&a),
+                    values: prefixes.iter().map(|b| b.values).sum(),
+                    tribles,
+                    histogram,
+                },
+            );
+        }
+        statistics
+    }
+
+    pub fn attribute(&self, attr: &Id) -> Option<&AttributeStatistics> {
+        self.attributes.get(attr)
+    }
+
+    /// Wraps `set`, so that its patterns estimate from these statistics,
+    /// see [Analyzed].
+    pub fn over<'a, T>(&'a self, set: &'a T) -> Analyzed<'a, T>
+    where
+        T: TriblePattern,
+    {
+        Analyzed {
+            set,
+            statistics: self,
+        }
+    }
+}
+
+/// A [TriblePattern] source whose pattern constraints take their estimates
+/// from [SetStatistics] when the attribute is bound and covered by them.
+///
+/// With a bound value the histogram estimates the matching entities,
+/// otherwise the attribute's entity and value counts are used. Proposals
+/// and confirmations always come from the wrapped source, so outdated
+/// statistics only affect the order in which variables are bound.
+pub struct Analyzed<'a, T> {
+    set: &'a T,
+    statistics: &'a SetStatistics,
+}
+
+impl<'a, T> TriblePattern for Analyzed<'a, T>
+where
+    T: TriblePattern,
+{
+    type PatternConstraint<'b, V>
+        = AnalyzedConstraint<'b, T::PatternConstraint<'b, V>>
+    where
+        V: Valuelike,
+        Self: 'b;
+
+    fn pattern<'b, V>(
+        &'b self,
+        e: Variable<Id>,
+        a: Variable<Id>,
+        v: Variable<V>,
+    ) -> Self::PatternConstraint<'b, V>
+    where
+        V: Valuelike,
+    {
+        AnalyzedConstraint {
+            variable_e: e.index,
+            variable_a: a.index,
+            variable_v: v.index,
+            constraint: self.set.pattern(e, a, v),
+            statistics: self.statistics,
+        }
+    }
+}
+
+pub struct AnalyzedConstraint<'a, C> {
+    variable_e: VariableId,
+    variable_a: VariableId,
+    variable_v: VariableId,
+    constraint: C,
+    statistics: &'a SetStatistics,
+}
+
+impl<'a, C> Constraint<'a> for AnalyzedConstraint<'a, C>
+where
+    C: Constraint<'a>,
+{
+    fn variables(&self) -> VariableSet {
+        self.constraint.variables()
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.constraint.variable(variable)
+    }
+
+    fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+        let stats = binding
+            .get(self.variable_a)
+            .and_then(|a| self.statistics.attribute(&id_from_value(a)));
+        if let Some(stats) = stats {
+            if binding.get(self.variable_e).is_none() {
+                match binding.get(self.variable_v) {
+                    Some(v) if variable == self.variable_e => return stats.estimate(&v) as usize,
+                    None if variable == self.variable_e => return stats.entities as usize,
+                    None if variable == self.variable_v => return stats.values as usize,
+                    _ => {}
+                }
+            }
+        }
+        self.constraint.estimate(variable, binding)
+    }
+
+    fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+        self.constraint.propose(variable, binding)
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+        self.constraint.confirm(variable, binding, proposals)
+    }
+}
+
+const ATTRIBUTE_HEADER_LEN: usize = ID_LEN + 4 * 8;
+const BUCKET_LEN: usize = VALUE_LEN + 2 * 8;
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+impl Bloblike for SetStatistics {
+    fn into_blob(self) -> Bytes {
+        let mut buffer = vec![];
+        for (a, stats) in &self.attributes {
+            buffer.extend_from_slice(a);
+            buffer.extend_from_slice(&stats.entities.to_be_bytes());
+            buffer.extend_from_slice(&stats.values.to_be_bytes());
+            buffer.extend_from_slice(&stats.tribles.to_be_bytes());
+            buffer.extend_from_slice(&(stats.histogram.len() as u64).to_be_bytes());
+            for bucket in &stats.histogram {
+                buffer.extend_from_slice(&bucket.upper);
+                buffer.extend_from_slice(&bucket.values.to_be_bytes());
+                buffer.extend_from_slice(&bucket.tribles.to_be_bytes());
+            }
+        }
+        buffer.into()
+    }
+
+    fn from_blob(blob: Bytes) -> Result<Self, BlobParseError> {
+        let mut statistics = SetStatistics::default();
+        let mut at = 0;
+        while at < blob.len() {
+            if blob.len() - at < ATTRIBUTE_HEADER_LEN {
+                return Err(BlobParseError::new("truncated attribute statistics"));
+            }
+            let a: Id = blob[at..at + ID_LEN].try_into().unwrap();
+            at += ID_LEN;
+            let entities = read_u64(&blob, at);
+            let values = read_u64(&blob, at + 8);
+            let tribles = read_u64(&blob, at + 16);
+            let buckets = read_u64(&blob, at + 24) as usize;
+            at += 32;
+
+            if (blob.len() - at) / BUCKET_LEN < buckets {
+                return Err(BlobParseError::new("truncated histogram"));
+            }
+            let mut histogram = Vec::with_capacity(buckets);
+            for _ in 0..buckets {
+                histogram.push(Bucket {
+                    upper: blob[at..at + VALUE_LEN].try_into().unwrap(),
+                    values: read_u64(&blob, at + VALUE_LEN),
+                    tribles: read_u64(&blob, at + VALUE_LEN + 8),
+                });
+                at += BUCKET_LEN;
+            }
+
+            statistics.attributes.insert(
+                a,
+                AttributeStatistics {
+                    entities,
+                    values,
+                    tribles,
+                    histogram,
+                },
+            );
+        }
+        Ok(statistics)
+    }
+
+    fn as_handle<H>(&self) -> Handle<H, Self>
+    where
+        H: Digest<OutputSize = U32>,
+    {
+        let digest = H::digest(&self.clone().into_blob());
+        unsafe { Handle::new(Hash::new(digest.into())) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::query::VariableContext;
+    use crate::{find, id_into_value, types::ShortString, ufoid, NS};
+
+    NS! {
+        pub namespace books {
+            "A4C09E1C33F0E0F3A2B9C7D6E5F40112" as genre: ShortString;
+            "B5D1AF2D44010104B3CAD8E7F6051223" as title: ShortString;
+            "C6E2B03E55121215C4DBE9F807162334" as author: Id;
+            "D7F3C14F66232326D5ECFA0918273445" as country: ShortString;
+        }
+    }
+
+    /// Estimates the entities with a value as if all values of an attribute
+    /// were equally common, like sources without per-value counts do, and
+    /// counts the proposed values.
+    struct Uniform<'a> {
+        set: &'a TribleSet,
+        proposed: Cell<usize>,
+    }
+
+    impl<'s> TriblePattern for Uniform<'s> {
+        type PatternConstraint<'a, V>
+            = UniformConstraint<'a, <TribleSet as TriblePattern>::PatternConstraint<'a, V>>
+        where
+            V: Valuelike,
+            Self: 'a;
+
+        fn pattern<'a, V>(
+            &'a self,
+            e: Variable<Id>,
+            a: Variable<Id>,
+            v: Variable<V>,
+        ) -> Self::PatternConstraint<'a, V>
+        where
+            V: Valuelike,
+        {
+            UniformConstraint {
+                variable_e: e.index,
+                variable_a: a.index,
+                variable_v: v.index,
+                constraint: self.set.pattern(e, a, v),
+                set: self.set,
+                proposed: &self.proposed,
+            }
+        }
+    }
+
+    struct UniformConstraint<'a, C> {
+        variable_e: VariableId,
+        variable_a: VariableId,
+        variable_v: VariableId,
+        constraint: C,
+        set: &'a TribleSet,
+        proposed: &'a Cell<usize>,
+    }
+
+    impl<'a, C> Constraint<'a> for UniformConstraint<'a, C>
+    where
+        C: Constraint<'a>,
+    {
+        fn variables(&self) -> VariableSet {
+            self.constraint.variables()
+        }
+
+        fn variable(&self, variable: VariableId) -> bool {
+            self.constraint.variable(variable)
+        }
+
+        fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+            let e = binding.get(self.variable_e);
+            let a = binding.get(self.variable_a);
+            let v = binding.get(self.variable_v);
+            match (e, a, v) {
+                (None, Some(a), Some(_)) if variable == self.variable_e => {
+                    let stats = self.set.attribute_stats(&id_from_value(a));
+                    (stats.tribles / stats.values.max(1)) as usize
+                }
+                _ => self.constraint.estimate(variable, binding),
+            }
+        }
+
+        fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+            let values = self.constraint.propose(variable, binding);
+            self.proposed.set(self.proposed.get() + values.len());
+            values
+        }
+
+        fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+            self.constraint.confirm(variable, binding, proposals)
+        }
+    }
+
+    fn fixture() -> TribleSet {
+        let mut set = TribleSet::new();
+        for i in 0..100 {
+            let genre = if i < 90 { "fantasy" } else { "poetry" };
+            set.union(books::entity!(ufoid(), {
+                genre: genre.try_into().unwrap(),
+                title: (&format!("Book {}", i)[..]).try_into().unwrap()
+            }));
+        }
+        set
+    }
+
+    #[test]
+    fn counts_match_fixture() {
+        let set = fixture();
+        let stats = SetStatistics::analyze(&set, None);
+        assert_eq!(stats.attributes.len(), 2);
+
+        let genre = stats.attribute(&books::ids::genre).unwrap();
+        assert_eq!(genre.entities, 100);
+        assert_eq!(genre.values, 2);
+        assert_eq!(genre.tribles, 100);
+
+        let fantasy: ShortString = "fantasy".try_into().unwrap();
+        let poetry: ShortString = "poetry".try_into().unwrap();
+        assert_eq!(genre.estimate(&Valuelike::into_value(&fantasy)), 90);
+        assert_eq!(genre.estimate(&Valuelike::into_value(&poetry)), 10);
+
+        let title = stats.attribute(&books::ids::title).unwrap();
+        assert_eq!(title.values, 100);
+        assert!(title.histogram.len() <= HISTOGRAM_BUCKETS);
+        assert_eq!(title.histogram.iter().map(|b| b.tribles).sum::<u64>(), 100);
+        assert!(title.histogram.windows(2).all(|w| w[0].upper < w[1].upper));
+    }
+
+    #[test]
+    fn histogram_skips_shared_bytes() {
+        // Ids are stored in the last 16 bytes of a value, after 16 zeros.
+        let authors: Vec<Id> = (0..40).map(|_| ufoid()).collect();
+        let mut set = TribleSet::new();
+        for i in 0..100 {
+            set.union(books::entity!(ufoid(), { author: authors[i % authors.len()] }));
+        }
+        let stats = SetStatistics::analyze(&set, None);
+        let author = stats.attribute(&books::ids::author).unwrap();
+        assert_eq!(author.values, 40);
+        assert!(author.histogram.len() > 1);
+        assert_eq!(author.histogram.iter().map(|b| b.values).sum::<u64>(), 40);
+        // Each author has two or three books.
+        for a in &authors {
+            let estimate = author.estimate(&id_into_value(*a));
+            assert!((2..=3).contains(&estimate), "estimated {}", estimate);
+        }
+    }
+
+    #[test]
+    fn selected_attributes() {
+        let set = fixture();
+        let stats = SetStatistics::analyze(&set, Some(&[books::ids::genre, ufoid()]));
+        assert_eq!(stats.attributes.len(), 1);
+        assert!(stats.attribute(&books::ids::genre).is_some());
+    }
+
+    #[test]
+    fn blob_roundtrip() {
+        let stats = SetStatistics::analyze(&fixture(), None);
+        let blob = stats.clone().into_blob();
+        assert_eq!(SetStatistics::from_blob(blob).unwrap(), stats);
+    }
+
+    #[test]
+    fn analyzed_estimates() {
+        let set = fixture();
+        let stats = SetStatistics::analyze(&set, None);
+        let analyzed = stats.over(&set);
+
+        let mut ctx = VariableContext::new();
+        let e: Variable<Id> = ctx.next_variable();
+        let a: Variable<Id> = ctx.next_variable();
+        let v: Variable<ShortString> = ctx.next_variable();
+        let constraint = analyzed.pattern(e, a, v);
+
+        let mut binding = Binding::default();
+        binding.set(a.index, id_into_value(books::ids::genre));
+        assert_eq!(constraint.estimate(e.index, &binding), 100);
+        assert_eq!(constraint.estimate(v.index, &binding), 2);
+
+        let poetry: ShortString = "poetry".try_into().unwrap();
+        binding.set(v.index, Valuelike::into_value(&poetry));
+        assert_eq!(constraint.estimate(e.index, &binding), 10);
+    }
+
+    #[test]
+    fn outdated_statistics_keep_results() {
+        let mut set = fixture();
+        let stats = SetStatistics::analyze(&set, None);
+        for _ in 0..50 {
+            set.union(books::entity!(ufoid(), { genre: "poetry".try_into().unwrap() }));
+        }
+
+        let plain = find!(
+            ctx,
+            (book, title),
+            books::pattern!(ctx, set, [{book @ genre: ("poetry".try_into().unwrap()), title: title}])
+        )
+        .count();
+        let analyzed = stats.over(&set);
+        let estimated = find!(
+            ctx,
+            (book, title),
+            books::pattern!(ctx, analyzed, [{book @ genre: ("poetry".try_into().unwrap()), title: title}])
+        )
+        .count();
+        assert_eq!(plain, 10);
+        assert_eq!(estimated, plain);
+    }
+
+    #[test]
+    fn skew_picks_better_plan() {
+        fn icelandic_poetry<T: TriblePattern>(source: &T) -> Vec<(Id, Id)> {
+            let mut rows: Vec<(Id, Id)> = find!(
+                ctx,
+                (book, author),
+                books::pattern!(ctx, source, [
+                    {book @ genre: ("poetry".try_into().unwrap()), author: author},
+                    {author @ country: ("Iceland".try_into().unwrap())}
+                ])
+            )
+            .map(|r| r.unwrap())
+            .collect();
+            rows.sort();
+            rows
+        }
+
+        // 90% of the books are fantasy and 75% of the authors are from
+        // Iceland, but assuming uniform values both look selective.
+        let authors: Vec<Id> = (0..40).map(|_| ufoid()).collect();
+        let mut set = TribleSet::new();
+        for (i, author) in authors.iter().enumerate() {
+            let country = if i % 4 != 0 {
+                "Iceland"
+            } else {
+                ["Chile", "Japan", "Kenya"][i / 4 % 3]
+            };
+            set.union(books::entity!(*author, { country: country.try_into().unwrap() }));
+        }
+        for i in 0..100 {
+            let genre = if i < 90 { "fantasy" } else { "poetry" };
+            set.union(books::entity!(ufoid(), {
+                genre: genre.try_into().unwrap(),
+                author: authors[i % authors.len()]
+            }));
+        }
+
+        let uniform = Uniform {
+            set: &set,
+            proposed: Cell::new(0),
+        };
+        let expected = icelandic_poetry(&uniform);
+        let uniform_proposed = uniform.proposed.replace(0);
+
+        let stats = SetStatistics::analyze(&set, None);
+        let analyzed = icelandic_poetry(&stats.over(&uniform));
+        let analyzed_proposed = uniform.proposed.get();
+
+        assert_eq!(analyzed, expected);
+        assert_eq!(expected.len(), 8);
+        assert!(
+            analyzed_proposed * 2 < uniform_proposed,
+            "statistics proposed {} values, uniform estimates {}",
+            analyzed_proposed,
+            uniform_proposed
+        );
+    }
+}
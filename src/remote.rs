@@ -1,3 +1,5 @@
+#[cfg(any(test, feature = "backend-tests"))]
+pub mod conformance;
 pub mod head;
 pub mod objectstore;
 pub mod repo;
@@ -0,0 +1,253 @@
+//! Behavioral checks shared by all [Repo](super::Repo) and [Head] implementations.
+//!
+//! Backends differ in how they store data, but the rest of the crate relies
+//! on the same semantics from all of them: pushes are idempotent and
+//! content addressed, pulling an unknown hash is an error, and commits to a
+//! head are compare-and-swap operations with exactly one winner.
+//!
+//! [run_repo_suite] and [run_head_suite] take a factory that creates a
+//! fresh, empty store and panic with a description of the first violated
+//! expectation, so they can be called directly from a backend's tests:
+//!
+//! ```ignore
+//! #[test]
+//! fn conformance() {
+//!     futures::executor::block_on(run_head_suite(|| MyHead::new()));
+//! }
+//! ```
+//!
+//! The suites are compiled with the `backend-tests` feature.
+
+use std::fmt::Debug;
+
+use anybytes::Bytes;
+use digest::{typenum::U32, Digest};
+use futures::{future::join_all, StreamExt};
+
+use crate::types::Hash;
+
+use super::head::{CommitResult, Head};
+use super::repo::{List, Pull, Push};
+
+/// The number of commits raced against each other when checking that
+/// compare-and-swap has a single winner.
+const CONCURRENT_COMMITS: u8 = 8;
+
+pub async fn run_repo_suite<R, H, F>(mut factory: F)
+where
+    F: FnMut() -> R,
+    R: List<H> + Pull<H> + Push<H>,
+    <R as List<H>>::Err: Debug,
+    <R as Pull<H>>::Err: Debug,
+    <R as Push<H>>::Err: Debug,
+    H: Digest<OutputSize = U32>,
+{
+    let repo = factory();
+    let blob: Bytes = b"conformance".to_vec().into();
+    let hash = repo.push(blob.clone()).await.expect("push failed");
+    assert_eq!(
+        hash,
+        Hash::digest(&blob),
+        "push must return the hash of the blob contents"
+    );
+    let pulled = repo.pull(hash).await.expect("pull of pushed blob failed");
+    assert_eq!(&pulled[..], &blob[..], "pull must return the pushed bytes");
+
+    let again = repo
+        .push(blob.clone())
+        .await
+        .expect("pushing an existing blob must succeed");
+    assert_eq!(
+        again, hash,
+        "pushing a blob twice must return the same hash"
+    );
+
+    let empty: Bytes = Vec::new().into();
+    let empty_hash = repo.push(empty).await.expect("push of empty blob failed");
+    let pulled = repo
+        .pull(empty_hash)
+        .await
+        .expect("pull of empty blob failed");
+    assert!(pulled.is_empty(), "empty blobs must round trip");
+
+    let mut listed: Vec<Hash<H>> = repo
+        .list()
+        .map(|r| r.expect("listing failed"))
+        .collect()
+        .await;
+    listed.sort_by_key(|h| h.bytes);
+    let mut expected = vec![hash, empty_hash];
+    expected.sort_by_key(|h| h.bytes);
+    assert_eq!(
+        listed, expected,
+        "list must return every pushed blob exactly once"
+    );
+
+    let repo = factory();
+    assert!(
+        repo.pull(hash).await.is_err(),
+        "pulling an unknown hash must fail"
+    );
+    assert_eq!(
+        repo.list().count().await,
+        0,
+        "a fresh repo must not list any blobs"
+    );
+}
+
+pub async fn run_head_suite<D, H, F>(mut factory: F)
+where
+    F: FnMut() -> D,
+    D: Head<H>,
+    D::CheckoutErr: Debug,
+    D::CommitErr: Debug,
+{
+    let a = Hash::<H>::new([1; 32]);
+    let b = Hash::<H>::new([2; 32]);
+    let c = Hash::<H>::new([3; 32]);
+
+    let head = factory();
+    assert!(
+        head.checkout().await.expect("checkout failed").is_none(),
+        "a fresh head must be empty"
+    );
+    match head.commit(Some(a), b).await.expect("commit failed") {
+        CommitResult::Conflict(None) => {}
+        _ => panic!("committing over an expected hash must conflict on an empty head"),
+    }
+    match head.commit(None, a).await.expect("commit failed") {
+        CommitResult::Success() => {}
+        _ => panic!("the first commit to an empty head must succeed"),
+    }
+    assert_eq!(
+        head.checkout().await.expect("checkout failed"),
+        Some(a),
+        "checkout must return the last successful commit"
+    );
+
+    match head.commit(None, b).await.expect("commit failed") {
+        CommitResult::Conflict(Some(current)) if current == a => {}
+        _ => panic!(
+            "committing to a set head without an old hash must conflict with the current hash"
+        ),
+    }
+    match head.commit(Some(b), c).await.expect("commit failed") {
+        CommitResult::Conflict(Some(current)) if current == a => {}
+        _ => panic!("committing with a stale old hash must conflict with the current hash"),
+    }
+    assert_eq!(
+        head.checkout().await.expect("checkout failed"),
+        Some(a),
+        "conflicting commits must not change the head"
+    );
+
+    match head.commit(Some(a), b).await.expect("commit failed") {
+        CommitResult::Success() => {}
+        _ => panic!("committing with the current hash as old hash must succeed"),
+    }
+    assert_eq!(
+        head.checkout().await.expect("checkout failed"),
+        Some(b),
+        "checkout must return the last successful commit"
+    );
+
+    let head = factory();
+    head.commit(None, a).await.expect("commit failed");
+    let candidates: Vec<Hash<H>> = (0..CONCURRENT_COMMITS)
+        .map(|i| Hash::new([0x10 + i; 32]))
+        .collect();
+    let results = join_all(candidates.iter().map(|&new| head.commit(Some(a), new))).await;
+    let winners: Vec<Hash<H>> = candidates
+        .iter()
+        .zip(results)
+        .filter_map(|(&new, r)| match r.expect("commit failed") {
+            CommitResult::Success() => Some(new),
+            CommitResult::Conflict(_) => None,
+        })
+        .collect();
+    assert_eq!(
+        winners.len(),
+        1,
+        "exactly one of {} concurrent commits over the same old hash must succeed",
+        CONCURRENT_COMMITS
+    );
+    assert_eq!(
+        head.checkout().await.expect("checkout failed"),
+        Some(winners[0]),
+        "the head must hold the winning commit after a race"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Mutex;
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+    use url::Url;
+
+    use super::*;
+    use crate::remote::objectstore::{ObjectHead, ObjectRepo};
+    use crate::types::hash::Blake3;
+
+    #[test]
+    fn object_repo() {
+        block_on(run_repo_suite(|| {
+            ObjectRepo::<Blake3>::with_url(&Url::parse("memory:///").unwrap()).unwrap()
+        }));
+    }
+
+    #[test]
+    fn object_head() {
+        block_on(run_head_suite(|| {
+            ObjectHead::<Blake3>::with_url(&Url::parse("memory:///head").unwrap()).unwrap()
+        }));
+    }
+
+    /// Returns `Pending` once, giving other futures a chance to run.
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// A head that checks and updates its value in two separate steps.
+    struct RacyHead<H>(Mutex<Option<Hash<H>>>);
+
+    impl<H> Head<H> for RacyHead<H> {
+        type CheckoutErr = ();
+        type CommitErr = ();
+
+        async fn checkout(&self) -> Result<Option<Hash<H>>, ()> {
+            Ok(*self.0.lock().unwrap())
+        }
+
+        async fn commit(&self, old: Option<Hash<H>>, new: Hash<H>) -> Result<CommitResult<H>, ()> {
+            let current = *self.0.lock().unwrap();
+            if current != old {
+                return Ok(CommitResult::Conflict(current));
+            }
+            YieldNow(false).await;
+            *self.0.lock().unwrap() = Some(new);
+            Ok(CommitResult::Success())
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "concurrent commits over the same old hash must succeed")]
+    fn racy_head_fails() {
+        block_on(run_head_suite(|| RacyHead::<Blake3>(Mutex::new(None))));
+    }
+}
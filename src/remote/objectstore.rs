@@ -26,6 +26,20 @@ pub struct ObjectRepo<H> {
 }
 
 impl<H> ObjectRepo<H> {
+    /// Stores blobs in `store` below `prefix`, named by the hex encoded hash
+    /// of their contents.
+    ///
+    /// Use this over [ObjectRepo::with_url] to share a store or to configure
+    /// it, e.g. with credentials or the retry and backoff policy of the
+    /// object store's client.
+    pub fn new(store: Box<dyn ObjectStore>, prefix: Path) -> ObjectRepo<H> {
+        ObjectRepo {
+            store,
+            prefix,
+            _hasher: PhantomData,
+        }
+    }
+
     pub fn with_url(url: &Url) -> Result<ObjectRepo<H>, object_store::Error> {
         let (store, path) = parse_url(&url)?;
         Ok(ObjectRepo {
@@ -36,12 +50,34 @@ impl<H> ObjectRepo<H> {
     }
 }
 
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum ListErr {
     List(object_store::Error),
     NotAFile(&'static str),
     BadNameHex(<Value as FromHex>::Error),
 }
 
+impl fmt::Display for ListErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::List(e) => write!(f, "list failed: {}", e),
+            Self::NotAFile(e) => write!(f, "list failed: {}", e),
+            Self::BadNameHex(e) => write!(f, "list failed: {}", e),
+        }
+    }
+}
+
+impl Error for ListErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::List(e) => Some(e),
+            Self::NotAFile(_) => None,
+            Self::BadNameHex(e) => Some(e),
+        }
+    }
+}
+
 impl<H> List<H> for ObjectRepo<H>
 where
     H: Digest<OutputSize = U32>,
@@ -111,6 +147,18 @@ pub struct ObjectHead<H> {
 }
 
 impl<H> ObjectHead<H> {
+    /// Stores the head in the object at `path` of `store`.
+    ///
+    /// Commits use conditional puts, so several heads on the same object,
+    /// even in different processes, only ever advance it by compare-and-swap.
+    pub fn new(store: Box<dyn ObjectStore>, path: Path) -> ObjectHead<H> {
+        ObjectHead {
+            store,
+            path,
+            _hasher: PhantomData,
+        }
+    }
+
     pub fn with_url(url: &Url) -> Result<ObjectHead<H>, object_store::Error> {
         let (store, path) = parse_url(&url)?;
         Ok(ObjectHead {
@@ -122,6 +170,7 @@ impl<H> ObjectHead<H> {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CheckoutErr {
     ValidationErr(TryFromSliceError),
     StoreErr(object_store::Error),
@@ -136,7 +185,14 @@ impl fmt::Display for CheckoutErr {
     }
 }
 
-impl Error for CheckoutErr {}
+impl Error for CheckoutErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ValidationErr(e) => Some(e),
+            Self::StoreErr(e) => Some(e),
+        }
+    }
+}
 
 impl From<object_store::Error> for CheckoutErr {
     fn from(err: object_store::Error) -> Self {
@@ -151,6 +207,7 @@ impl From<TryFromSliceError> for CheckoutErr {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CommitErr {
     ValidationErr(TryFromSliceError),
     StoreErr(object_store::Error),
@@ -165,7 +222,14 @@ impl fmt::Display for CommitErr {
     }
 }
 
-impl Error for CommitErr {}
+impl Error for CommitErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ValidationErr(e) => Some(e),
+            Self::StoreErr(e) => Some(e),
+        }
+    }
+}
 
 impl From<object_store::Error> for CommitErr {
     fn from(err: object_store::Error) -> Self {
@@ -275,3 +339,59 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::executor::block_on;
+    use object_store::memory::InMemory;
+
+    use super::*;
+    use crate::types::hash::Blake3;
+
+    #[test]
+    fn shared_store() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let writer = ObjectRepo::<Blake3>::new(Box::new(store.clone()), Path::from("blobs"));
+        let reader = ObjectRepo::<Blake3>::new(Box::new(store.clone()), Path::from("blobs"));
+
+        let blob: Bytes = b"shared".to_vec().into();
+        let hash = block_on(writer.push(blob.clone())).unwrap();
+        assert_eq!(&block_on(reader.pull(hash)).unwrap()[..], &blob[..]);
+    }
+
+    #[test]
+    fn concurrent_heads_conflict() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let path = Path::from("heads/main");
+        let ours = ObjectHead::<Blake3>::new(Box::new(store.clone()), path.clone());
+        let theirs = ObjectHead::<Blake3>::new(Box::new(store.clone()), path);
+
+        let (a, b, c) = (
+            Hash::<Blake3>::new([1; 32]),
+            Hash::<Blake3>::new([2; 32]),
+            Hash::<Blake3>::new([3; 32]),
+        );
+        assert!(matches!(
+            block_on(ours.commit(None, a)).unwrap(),
+            CommitResult::Success()
+        ));
+        assert!(matches!(
+            block_on(theirs.commit(None, b)).unwrap(),
+            CommitResult::Conflict(Some(current)) if current == a
+        ));
+
+        let seen = block_on(theirs.checkout()).unwrap();
+        assert_eq!(seen, Some(a));
+        assert!(matches!(
+            block_on(theirs.commit(seen, b)).unwrap(),
+            CommitResult::Success()
+        ));
+        assert!(matches!(
+            block_on(ours.commit(Some(a), c)).unwrap(),
+            CommitResult::Conflict(Some(current)) if current == b
+        ));
+        assert_eq!(block_on(ours.checkout()).unwrap(), Some(b));
+    }
+}
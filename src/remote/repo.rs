@@ -11,6 +11,7 @@ use anybytes::Bytes;
 use crate::{types::Hash, BlobParseError, BlobSet};
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum TransferError<ListErr, LoadErr, StoreErr> {
     List(ListErr),
     Load(LoadErr),
@@ -72,11 +73,33 @@ where
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum GetError<E> {
     Load(E),
     Parse(BlobParseError),
 }
 
+impl<E> fmt::Display for GetError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(_) => write!(f, "failed to load blob"),
+            Self::Parse(_) => write!(f, "failed to parse loaded blob"),
+        }
+    }
+}
+
+impl<E> Error for GetError<E>
+where
+    E: Debug + Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Load(e) => Some(e),
+            Self::Parse(e) => Some(e),
+        }
+    }
+}
+
 pub trait List<H> {
     type Err;
 
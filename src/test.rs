@@ -1 +1,2 @@
 pub mod hashtribleset;
+pub mod setdiff;
@@ -1,5 +1,5 @@
 use crate::{
-    query::{Binding, Constraint, Variable, VariableId, VariableSet},
+    query::{Binding, Constraint, ConstraintDescription, Variable, VariableId, VariableSet},
     trible::Trible,
 };
 
@@ -402,4 +402,11 @@ where
             }
         }
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "hash tribleset {} {} {}",
+            self.variable_e, self.variable_a, self.variable_v
+        ))
+    }
 }
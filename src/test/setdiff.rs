@@ -0,0 +1,120 @@
+//! Readable failure output for comparing [TribleSet]s in tests.
+//!
+//! `assert_eq!` on two sets only prints their `Debug` representation, which
+//! is a dump of the PATCH indices. [assert_sets_equal] instead prints the
+//! tribles that only occur on one side, grouped by entity:
+//!
+//! ```should_panic
+//! use std::convert::TryInto;
+//! use tribles::{assert_sets_equal, types::ShortString, ufoid, NS};
+//!
+//! NS! {
+//!     pub namespace knights {
+//!         "C4C1C9A8F6F34E8D9F1D1A2B3C4D5E6F" as name: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! let romeo = ufoid();
+//! let left = knights::entity!(romeo, { name: "Romeo".try_into().unwrap() });
+//! let right = knights::entity!(romeo, { name: "Juliet".try_into().unwrap() });
+//!
+//! // Panics with a `-` line for "Romeo" and a `+` line for "Juliet",
+//! // both listed under the entity id of `romeo`.
+//! assert_sets_equal!(left, right);
+//! ```
+
+use std::fmt::Write;
+
+use crate::trible::checkpoints::TribleDiff;
+use crate::trible::{A_END, A_START, E_END, E_START, TRIBLE_LEN, V_END, V_START};
+use crate::TribleSet;
+
+/// Lists the tribles that are only in `left` (`-`) or only in `right`
+/// (`+`), grouped by entity. Returns an empty string for equal sets.
+pub fn describe_difference(left: &TribleSet, right: &TribleSet) -> String {
+    let diff = TribleDiff::new(left, right);
+    let mut lines: Vec<([u8; TRIBLE_LEN], char)> = diff
+        .removed
+        .iter_ordered()
+        .map(|t| (t, '-'))
+        .chain(diff.added.iter_ordered().map(|t| (t, '+')))
+        .collect();
+    lines.sort_unstable();
+
+    let mut out = String::new();
+    let mut entity = None;
+    for (t, sign) in lines {
+        let e = &t[E_START..=E_END];
+        if entity != Some(e.to_vec()) {
+            writeln!(out, "entity {}:", hex::encode_upper(e)).unwrap();
+            entity = Some(e.to_vec());
+        }
+        writeln!(
+            out,
+            "  {} {} {}",
+            sign,
+            hex::encode_upper(&t[A_START..=A_END]),
+            hex::encode_upper(&t[V_START..=V_END])
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Asserts that two [TribleSet]s are equal, printing the tribles that only
+/// occur in one of them otherwise.
+#[macro_export]
+macro_rules! assert_sets_equal {
+    ($left:expr, $right:expr $(,)?) => {{
+        let (left, right): (&$crate::TribleSet, &$crate::TribleSet) = (&$left, &$right);
+        if left != right {
+            panic!(
+                "sets are not equal (- left only, + right only):\n{}",
+                $crate::test::setdiff::describe_difference(left, right)
+            );
+        }
+    }};
+}
+
+pub use assert_sets_equal;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trible::Trible;
+
+    #[test]
+    fn equal_sets() {
+        let set = TribleSet::singleton(&Trible::new([1; 16], [2; 16], [3; 32]));
+        assert_eq!(describe_difference(&set, &set.clone()), "");
+        assert_sets_equal!(set, set.clone());
+    }
+
+    #[test]
+    fn grouped_by_entity() {
+        let mut left = TribleSet::new();
+        left.insert(&Trible::new([1; 16], [2; 16], [3; 32]));
+        left.insert(&Trible::new([5; 16], [2; 16], [3; 32]));
+        let mut right = left.clone();
+        right.insert(&Trible::new([1; 16], [2; 16], [4; 32]));
+        left.insert(&Trible::new([1; 16], [6; 16], [7; 32]));
+
+        let expected = format!(
+            "entity {e}:\n  + {a} {v4}\n  - {b} {v7}\n",
+            e = "01".repeat(16),
+            a = "02".repeat(16),
+            v4 = "04".repeat(32),
+            b = "06".repeat(16),
+            v7 = "07".repeat(32),
+        );
+        assert_eq!(describe_difference(&left, &right), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "+ 0202020202020202")]
+    fn failing_assertion() {
+        let left = TribleSet::singleton(&Trible::new([1; 16], [2; 16], [3; 32]));
+        let right = TribleSet::singleton(&Trible::new([1; 16], [2; 16], [4; 32]));
+        assert_sets_equal!(left, right);
+    }
+}
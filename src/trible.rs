@@ -1,3 +1,9 @@
+pub mod checkpoints;
+pub mod migrate;
+pub mod partial;
+pub mod staging;
+pub mod stream;
+
 use std::convert::TryInto;
 
 use crate::{
@@ -0,0 +1,243 @@
+//! Named in-memory checkpoints of evolving [TribleSet]s.
+//!
+//! Cloning a [TribleSet] only increments the reference counts of the
+//! index roots, so a checkpoint shares all of its structure with the set it
+//! was taken from and costs O(1) to create, no matter how large the set is.
+
+use digest::{typenum::U32, Digest};
+
+use crate::blobset::BlobSet;
+use crate::trible::TRIBLE_LEN;
+use crate::triblearchive::SimpleArchive;
+use crate::{BlobParseError, Handle, TribleSet};
+
+/// The tribles that have to be added to and removed from one
+/// [TribleSet] to turn it into another.
+#[derive(Debug, Clone)]
+pub struct TribleDiff {
+    pub added: TribleSet,
+    pub removed: TribleSet,
+}
+
+impl TribleDiff {
+    pub fn new(from: &TribleSet, to: &TribleSet) -> Self {
+        TribleDiff {
+            added: missing_from(to, from),
+            removed: missing_from(from, to),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.len() == 0 && self.removed.len() == 0
+    }
+}
+
+fn missing_from(set: &TribleSet, other: &TribleSet) -> TribleSet {
+    let mut missing = TribleSet::new();
+    for t in set.iter_ordered() {
+        if !other.contains_raw(&t) {
+            missing.insert_raw(&t);
+        }
+    }
+    missing
+}
+
+/// A collection of named [TribleSet] snapshots.
+///
+/// Checkpoints are kept in the order they were saved, and once one of the
+/// optional limits is exceeded the oldest checkpoints are evicted first.
+/// The byte limit is measured in the size of the canonical
+/// [SimpleArchive] serialization, i.e. 64 bytes per trible, and ignores the
+/// structure that checkpoints share with each other.
+#[derive(Debug, Clone, Default)]
+pub struct CheckpointStore {
+    checkpoints: Vec<(String, TribleSet)>,
+    max_count: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl CheckpointStore {
+    pub fn new() -> Self {
+        CheckpointStore::default()
+    }
+
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self.evict();
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self.evict();
+        self
+    }
+
+    /// Saves a snapshot of `set` under `name`, replacing any previous
+    /// checkpoint with that name.
+    pub fn save(&mut self, name: &str, set: &TribleSet) {
+        self.checkpoints.retain(|(n, _)| n != name);
+        self.checkpoints.push((name.to_owned(), set.clone()));
+        self.evict();
+    }
+
+    pub fn restore(&self, name: &str) -> Option<TribleSet> {
+        self.get(name).cloned()
+    }
+
+    /// Computes the changes leading from checkpoint `name_a` to `name_b`.
+    pub fn diff(&self, name_a: &str, name_b: &str) -> Option<TribleDiff> {
+        Some(TribleDiff::new(self.get(name_a)?, self.get(name_b)?))
+    }
+
+    /// Lists the checkpoint names from oldest to newest.
+    pub fn list<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        self.checkpoints.iter().map(|(n, _)| &n[..])
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<TribleSet> {
+        let i = self.checkpoints.iter().position(|(n, _)| n == name)?;
+        Some(self.checkpoints.remove(i).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Stores every checkpoint as a [SimpleArchive] in `blobs`, returning
+    /// the names and handles needed to load them again.
+    pub fn persist_to<H>(
+        &self,
+        blobs: &mut BlobSet<H>,
+    ) -> Vec<(String, Handle<H, SimpleArchive>)>
+    where
+        H: Digest<OutputSize = U32>,
+    {
+        self.checkpoints
+            .iter()
+            .map(|(name, set)| (name.clone(), blobs.put(SimpleArchive::from(set))))
+            .collect()
+    }
+
+    /// Loads the checkpoints previously stored with [CheckpointStore::persist_to].
+    pub fn load_from<H>(
+        blobs: &BlobSet<H>,
+        persisted: impl IntoIterator<Item = (String, Handle<H, SimpleArchive>)>,
+    ) -> Result<CheckpointStore, BlobParseError>
+    where
+        H: Digest<OutputSize = U32>,
+    {
+        let mut store = CheckpointStore::new();
+        for (name, handle) in persisted {
+            let archive = blobs
+                .get(handle)
+                .ok_or_else(|| BlobParseError::new("missing checkpoint archive"))??;
+            store.save(&name, &TribleSet::from(&archive));
+        }
+        Ok(store)
+    }
+
+    fn get(&self, name: &str) -> Option<&TribleSet> {
+        self.checkpoints
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, set)| set)
+    }
+
+    fn evict(&mut self) {
+        if let Some(max_count) = self.max_count {
+            let excess = self.checkpoints.len().saturating_sub(max_count);
+            self.checkpoints.drain(0..excess);
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            let mut bytes: usize = self
+                .checkpoints
+                .iter()
+                .map(|(_, set)| set.len() * TRIBLE_LEN)
+                .sum();
+            while bytes > max_bytes && !self.checkpoints.is_empty() {
+                let (_, set) = self.checkpoints.remove(0);
+                bytes -= set.len() * TRIBLE_LEN;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_sets_equal;
+    use crate::trible::Trible;
+    use crate::types::hash::Blake3;
+
+    fn trible(e: u8, v: u8) -> Trible {
+        let mut data = [0; TRIBLE_LEN];
+        data[15] = e;
+        data[31] = 1;
+        data[63] = v;
+        Trible { data }
+    }
+
+    #[test]
+    fn save_mutate_diff_restore() {
+        let mut store = CheckpointStore::new();
+        let mut set: TribleSet = (1..=100).map(|i| trible(i, 0)).collect();
+
+        store.save("before-cleanup", &set);
+        set.insert(&trible(101, 0));
+        let mut cleaned: TribleSet = (2..=101).map(|i| trible(i, 0)).collect();
+        cleaned.insert(&trible(1, 1));
+        store.save("after-cleanup", &cleaned);
+
+        assert_eq!(
+            store.list().collect::<Vec<_>>(),
+            vec!["before-cleanup", "after-cleanup"]
+        );
+
+        let diff = store.diff("before-cleanup", "after-cleanup").unwrap();
+        let added: TribleSet = vec![trible(1, 1), trible(101, 0)].into_iter().collect();
+        let removed: TribleSet = vec![trible(1, 0)].into_iter().collect();
+        assert_sets_equal!(diff.added, added);
+        assert_sets_equal!(diff.removed, removed);
+
+        let restored = store.restore("before-cleanup").unwrap();
+        assert_eq!(restored.len(), 100);
+        assert!(store.diff("before-cleanup", "before-cleanup").unwrap().is_empty());
+        assert!(store.restore("missing").is_none());
+    }
+
+    #[test]
+    fn eviction() {
+        let set: TribleSet = (1..=10).map(|i| trible(i, 0)).collect();
+
+        let mut by_count = CheckpointStore::new().with_max_count(2);
+        by_count.save("a", &set);
+        by_count.save("b", &set);
+        by_count.save("c", &set);
+        assert_eq!(by_count.list().collect::<Vec<_>>(), vec!["b", "c"]);
+
+        let mut by_bytes = CheckpointStore::new().with_max_bytes(25 * TRIBLE_LEN);
+        by_bytes.save("a", &set);
+        by_bytes.save("b", &set);
+        by_bytes.save("a", &set);
+        by_bytes.save("c", &set);
+        assert_eq!(by_bytes.list().collect::<Vec<_>>(), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn persistence_roundtrip() {
+        let mut store = CheckpointStore::new();
+        let a: TribleSet = (1..=10).map(|i| trible(i, 0)).collect();
+        let b: TribleSet = (5..=20).map(|i| trible(i, 1)).collect();
+        store.save("a", &a);
+        store.save("b", &b);
+
+        let mut blobs: BlobSet<Blake3> = BlobSet::new();
+        let persisted = store.persist_to(&mut blobs);
+        let loaded = CheckpointStore::load_from(&blobs, persisted).unwrap();
+
+        assert_eq!(loaded.list().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_sets_equal!(loaded.restore("a").unwrap(), a);
+        assert_eq!(loaded.restore("b").unwrap(), b);
+    }
+}
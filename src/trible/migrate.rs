@@ -0,0 +1,286 @@
+//! Rewriting the values of attributes whose value type has changed.
+//!
+//! A [Migration] maps attribute ids to converters on raw [Value]s and can
+//! optionally move the converted values to a new attribute.
+//! [apply_migration] produces a migrated copy of a [TribleSet], leaving the
+//! original untouched, and reports every value it converted or failed to
+//! convert. Values that fail to convert are kept unchanged, so a partially
+//! failed migration never loses data.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::trible::{Trible, E_END, E_START, V_END, V_START};
+use crate::{Id, TribleSet, Value, ValueParseError};
+
+type Converter = Box<dyn Fn(Value) -> Result<Value, ValueParseError>>;
+
+struct AttributeMigration {
+    target: Id,
+    converter: Converter,
+}
+
+#[derive(Default)]
+pub struct Migration {
+    attributes: HashMap<Id, AttributeMigration>,
+}
+
+impl Migration {
+    pub fn new() -> Self {
+        Migration::default()
+    }
+
+    /// Converts the values of `attribute` in place.
+    pub fn convert<F>(self, attribute: Id, converter: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, ValueParseError> + 'static,
+    {
+        self.convert_to(attribute, attribute, converter)
+    }
+
+    /// Converts the values of `attribute` and stores them under `target`.
+    pub fn convert_to<F>(mut self, attribute: Id, target: Id, converter: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, ValueParseError> + 'static,
+    {
+        self.attributes.insert(
+            attribute,
+            AttributeMigration {
+                target,
+                converter: Box::new(converter),
+            },
+        );
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct MigrationReport {
+    /// The `(entity, attribute, old value)` triples that were rewritten.
+    pub converted: Vec<(Id, Id, Value)>,
+    /// The `(entity, attribute, value)` triples that the converter left
+    /// unchanged and that kept their attribute.
+    pub skipped: Vec<(Id, Id, Value)>,
+    /// The `(entity, attribute, error)` triples that failed to convert
+    /// and were kept as they were.
+    pub failed: Vec<(Id, Id, ValueParseError)>,
+}
+
+impl MigrationReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// A migration that can't be applied at all, as opposed to single values
+/// that fail to convert, which are listed in the [MigrationReport].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum MigrationError {
+    /// The values of `attribute` are moved to `target`, whose values are
+    /// migrated as well, so the outcome would depend on the order in which
+    /// the attributes are converted.
+    ChainedTarget { attribute: Id, target: Id },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChainedTarget { attribute, target } => write!(
+                f,
+                "attribute {} is moved to {}, which is migrated itself",
+                hex::encode_upper(attribute),
+                hex::encode_upper(target)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// Applies `migration` to a copy of `set`.
+///
+/// Only the tribles of migrated attributes are visited, by scanning their
+/// ranges of the AEV index. The copy shares all unaffected subtrees with
+/// `set`; converted tribles are removed from it with
+/// [TribleSet::difference] and their replacements are inserted afterwards.
+pub fn apply_migration(
+    set: &TribleSet,
+    migration: &Migration,
+) -> Result<(TribleSet, MigrationReport), MigrationError> {
+    let mut attributes: Vec<_> = migration.attributes.iter().collect();
+    attributes.sort_by_key(|(a, _)| **a);
+    for (&attribute, m) in &attributes {
+        if m.target != attribute && migration.attributes.contains_key(&m.target) {
+            return Err(MigrationError::ChainedTarget {
+                attribute,
+                target: m.target,
+            });
+        }
+    }
+
+    let mut removed = TribleSet::new();
+    let mut added = TribleSet::new();
+    let mut report = MigrationReport::default();
+
+    let indices = set.indices();
+    for (&a, m) in attributes {
+        for data in indices.attribute_tribles(&a) {
+            let e: Id = data[E_START..=E_END].try_into().unwrap();
+            let v: Value = data[V_START..=V_END].try_into().unwrap();
+            match (m.converter)(v) {
+                Ok(new_v) if new_v == v && m.target == a => {
+                    report.skipped.push((e, a, v));
+                }
+                Ok(new_v) => {
+                    removed.insert_raw(&data);
+                    added.insert(&Trible::new(e, m.target, new_v));
+                    report.converted.push((e, a, v));
+                }
+                Err(err) => {
+                    report.failed.push((e, a, err));
+                }
+            }
+        }
+    }
+
+    let mut migrated = if removed.len() == 0 {
+        set.clone()
+    } else {
+        set.difference(&removed)
+    };
+    migrated.union(added);
+    Ok((migrated, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assert_sets_equal, find, types::ShortString, ufoid, Valuelike, NS};
+
+    NS! {
+        pub namespace literature {
+            "9C5B4B1F3A6E4E1B2E0A5C4D8F7B6A91" as title: ShortString;
+            "D2E6E1B8A3C94F0B7A1E5D3C2B4F6A80" as page_count_text: ShortString;
+            "4F7A2C9E1B3D5A6C8E0F2A4B6C8D0E1F" as page_count: Value;
+        }
+    }
+
+    fn u64_value(n: u64) -> Value {
+        let mut v = [0; 32];
+        v[24..32].copy_from_slice(&n.to_be_bytes());
+        v
+    }
+
+    fn text_to_u64(v: Value) -> Result<Value, ValueParseError> {
+        let s = ShortString::from_value(v)?;
+        let s: &str = (&s).into();
+        s.parse::<u64>()
+            .map(u64_value)
+            .map_err(|_| ValueParseError::new(v, "not a decimal number"))
+    }
+
+    #[test]
+    fn migrate_numeric_encoding() {
+        let mut set = TribleSet::new();
+        let mut books = vec![];
+        for i in 0..10u64 {
+            let book = ufoid();
+            books.push(book);
+            set.union(literature::entity!(book, {
+                title: (&format!("Book {}", i)[..]).try_into().unwrap(),
+                page_count_text: (&format!("{}", 100 + i)[..]).try_into().unwrap()
+            }));
+        }
+        let broken = ufoid();
+        set.union(literature::entity!(broken, {
+            title: "Broken".try_into().unwrap(),
+            page_count_text: "many".try_into().unwrap()
+        }));
+
+        let migration = Migration::new().convert_to(
+            literature::ids::page_count_text,
+            literature::ids::page_count,
+            text_to_u64,
+        );
+        let (migrated, report) = apply_migration(&set, &migration).unwrap();
+
+        assert_eq!(report.converted.len(), 10);
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, broken);
+        assert!(!report.is_success());
+
+        // Unaffected and failed tribles are kept, converted ones are moved.
+        assert_eq!(migrated.len(), set.len());
+
+        for (i, book) in books.iter().enumerate() {
+            let r: Vec<_> = find!(
+                ctx,
+                (count),
+                literature::pattern!(ctx, migrated, [{(*book) @ page_count: count}])
+            )
+            .collect();
+            assert_eq!(r, vec![Ok((u64_value(100 + i as u64),))]);
+        }
+
+        let r: Vec<_> = find!(
+            ctx,
+            (text),
+            literature::pattern!(ctx, migrated, [{(broken) @ page_count_text: text}])
+        )
+        .collect();
+        assert_eq!(r, vec![Ok(("many".try_into().unwrap(),))]);
+    }
+
+    #[test]
+    fn unchanged_values_are_skipped() {
+        let set = literature::entity!({ title: "Unchanged".try_into().unwrap() });
+        let migration = Migration::new().convert(literature::ids::title, Ok);
+        let (migrated, report) = apply_migration(&set, &migration).unwrap();
+
+        assert_sets_equal!(migrated, set);
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.converted.is_empty());
+    }
+
+    #[test]
+    fn convert_in_place() {
+        let (dune, book) = (ufoid(), ufoid());
+        let mut set = literature::entity!(dune, { title: "Dune".try_into().unwrap() });
+        set.union(literature::entity!(book, {
+            page_count_text: "412".try_into().unwrap()
+        }));
+        let migration = Migration::new().convert(literature::ids::page_count_text, text_to_u64);
+        let (migrated, report) = apply_migration(&set, &migration).unwrap();
+
+        assert_eq!(report.converted.len(), 1);
+        assert_eq!(report.converted[0].0, book);
+
+        let mut expected = literature::entity!(dune, { title: "Dune".try_into().unwrap() });
+        expected.insert(&Trible::new(
+            book,
+            literature::ids::page_count_text,
+            u64_value(412),
+        ));
+        assert_sets_equal!(migrated, expected);
+    }
+
+    #[test]
+    fn chained_targets_are_rejected() {
+        let set = TribleSet::new();
+        let migration = Migration::new()
+            .convert_to(
+                literature::ids::page_count_text,
+                literature::ids::page_count,
+                text_to_u64,
+            )
+            .convert(literature::ids::page_count, Ok);
+
+        assert!(matches!(
+            apply_migration(&set, &migration),
+            Err(MigrationError::ChainedTarget { .. })
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+//! A memory bounded cache of entities in front of a larger dataset.
+//!
+//! A [PartialSet] only keeps the tribles of recently used entities in
+//! memory and fetches the others on demand from an [EntityLoader], e.g.
+//! an archive that is too large to be indexed as a whole. When the cached
+//! tribles exceed the byte budget, the least recently used entities are
+//! evicted.
+//!
+//! Patterns are answered from the cache whenever their entity is bound,
+//! so queries that start from known entity ids, or join to them, work
+//! well. Patterns over unbound entities can't be answered per entity and
+//! fall back to [EntityLoader::scan], which bypasses the cache.
+
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::query::{
+    Binding, Constraint, ConstraintDescription, TriblePattern, Variable, VariableId, VariableSet,
+};
+use crate::trible::TRIBLE_LEN;
+use crate::{id_from_value, Id, TribleSet, Value, Valuelike};
+
+/// The dataset behind a [PartialSet].
+pub trait EntityLoader {
+    /// Returns all tribles of `entity`, or `None` if there are none.
+    fn load(&self, entity: &Id) -> Option<TribleSet>;
+
+    /// Returns all tribles, for patterns whose entity is not bound.
+    fn scan(&self) -> TribleSet;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PartialSetStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    entities: HashMap<Id, (TribleSet, u64)>,
+    recency: BTreeMap<u64, Id>,
+    tick: u64,
+    tribles: usize,
+    stats: PartialSetStats,
+}
+
+pub struct PartialSet<L> {
+    loader: L,
+    budget: usize,
+    cache: RefCell<Cache>,
+}
+
+impl<L> PartialSet<L>
+where
+    L: EntityLoader,
+{
+    /// Creates an empty cache that holds at most `budget` bytes of tribles,
+    /// but always at least the most recently used entity.
+    pub fn new(loader: L, budget: usize) -> Self {
+        PartialSet {
+            loader,
+            budget,
+            cache: RefCell::new(Cache::default()),
+        }
+    }
+
+    /// Returns the tribles of `entity`, loading and caching them if needed.
+    pub fn entity(&self, entity: &Id) -> TribleSet {
+        let mut cache = self.cache.borrow_mut();
+        let cache = &mut *cache;
+        cache.tick += 1;
+        let tick = cache.tick;
+
+        if let Some((set, used)) = cache.entities.get_mut(entity) {
+            let set = set.clone();
+            let previous = std::mem::replace(used, tick);
+            cache.recency.remove(&previous);
+            cache.recency.insert(tick, *entity);
+            cache.stats.hits += 1;
+            return set;
+        }
+
+        cache.stats.misses += 1;
+        // Missing entities are cached as empty sets, so that repeated
+        // lookups don't hit the loader either.
+        let set = self.loader.load(entity).unwrap_or_else(TribleSet::new);
+        cache.tribles += set.len();
+        cache.entities.insert(*entity, (set.clone(), tick));
+        cache.recency.insert(tick, *entity);
+
+        while cache.tribles * TRIBLE_LEN > self.budget && cache.entities.len() > 1 {
+            let (_, lru) = cache.recency.pop_first().unwrap();
+            let (evicted, _) = cache.entities.remove(&lru).unwrap();
+            cache.tribles -= evicted.len();
+            cache.stats.evictions += 1;
+        }
+
+        set
+    }
+
+    /// The number of bytes of tribles currently held in memory.
+    pub fn cached_bytes(&self) -> usize {
+        self.cache.borrow().tribles * TRIBLE_LEN
+    }
+
+    pub fn stats(&self) -> PartialSetStats {
+        self.cache.borrow().stats
+    }
+}
+
+impl<L> TriblePattern for PartialSet<L>
+where
+    L: EntityLoader,
+{
+    type PatternConstraint<'a, V>
+     = PartialSetConstraint<'a, V, L>
+     where V: Valuelike,
+           L: 'a;
+
+    fn pattern<'a, V>(
+        &'a self,
+        e: Variable<Id>,
+        a: Variable<Id>,
+        v: Variable<V>,
+    ) -> Self::PatternConstraint<'a, V>
+    where
+        V: Valuelike,
+    {
+        PartialSetConstraint {
+            variable_e: e,
+            variable_a: a,
+            variable_v: v,
+            set: self,
+            scan: OnceCell::new(),
+        }
+    }
+}
+
+pub struct PartialSetConstraint<'a, V, L> {
+    variable_e: Variable<Id>,
+    variable_a: Variable<Id>,
+    variable_v: Variable<V>,
+    set: &'a PartialSet<L>,
+    scan: OnceCell<TribleSet>,
+}
+
+impl<'a, V, L> PartialSetConstraint<'a, V, L>
+where
+    V: Valuelike,
+    L: EntityLoader,
+{
+    /// The tribles relevant for `binding`: the cached entity if it is bound
+    /// and a full scan otherwise.
+    fn tribles(&self, binding: &Binding) -> TribleSet {
+        match binding.get(self.variable_e.index) {
+            Some(e) => self.set.entity(&id_from_value(e)),
+            None => self.scan.get_or_init(|| self.set.loader.scan()).clone(),
+        }
+    }
+}
+
+impl<'a, V, L> Constraint<'a> for PartialSetConstraint<'a, V, L>
+where
+    V: Valuelike,
+    L: EntityLoader,
+{
+    fn variables(&self) -> VariableSet {
+        let mut variables = VariableSet::new_empty();
+        variables.set(self.variable_e.index);
+        variables.set(self.variable_a.index);
+        variables.set(self.variable_v.index);
+        variables
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.variable_e.index == variable
+            || self.variable_a.index == variable
+            || self.variable_v.index == variable
+    }
+
+    fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+        // Estimating without a bound entity would require a scan, so let
+        // other constraints bind the entity first if they can.
+        if binding.get(self.variable_e.index).is_none() && self.scan.get().is_none() {
+            return usize::MAX;
+        }
+        let tribles = self.tribles(binding);
+        tribles
+            .pattern(self.variable_e, self.variable_a, self.variable_v)
+            .estimate(variable, binding)
+    }
+
+    fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+        let tribles = self.tribles(binding);
+        tribles
+            .pattern(self.variable_e, self.variable_a, self.variable_v)
+            .propose(variable, binding)
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposals: &mut Vec<Value>) {
+        if binding.get(self.variable_e.index).is_none() && self.scan.get().is_none() {
+            if variable != self.variable_e.index {
+                // The proposals are checked once the entity is bound,
+                // which is cheaper than a scan.
+                return;
+            }
+            // Proposed entities can be checked one by one against their own
+            // tribles, without a scan.
+            proposals.retain(|e| {
+                let mut proposal = vec![*e];
+                self.set
+                    .entity(&id_from_value(*e))
+                    .pattern(self.variable_e, self.variable_a, self.variable_v)
+                    .confirm(variable, binding, &mut proposal);
+                !proposal.is_empty()
+            });
+            return;
+        }
+        let tribles = self.tribles(binding);
+        tribles
+            .pattern(self.variable_e, self.variable_a, self.variable_v)
+            .confirm(variable, binding, proposals)
+    }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "partial set {} {} {}",
+            self.variable_e, self.variable_a, self.variable_v
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::{find, types::ShortString, ufoid, NS};
+
+    NS! {
+        pub namespace knights {
+            "5C8E1A3F7B2D4E6A9C0B1D2E3F4A5B6C" as loves: Id;
+            "6D9F2B4A8C3E5F7B0D1C2E3F4A5B6C7D" as name: ShortString;
+        }
+    }
+
+    struct Reference {
+        set: TribleSet,
+        loads: Cell<usize>,
+        scans: Cell<usize>,
+    }
+
+    impl EntityLoader for &Reference {
+        fn load(&self, entity: &Id) -> Option<TribleSet> {
+            self.loads.set(self.loads.get() + 1);
+            self.set.entity(*entity).map(|view| view.to_set())
+        }
+
+        fn scan(&self) -> TribleSet {
+            self.scans.set(self.scans.get() + 1);
+            self.set.clone()
+        }
+    }
+
+    fn couples(n: usize) -> (TribleSet, Vec<(Id, Id)>) {
+        let mut set = TribleSet::new();
+        let mut couples = vec![];
+        for i in 0..n {
+            let (a, b) = (ufoid(), ufoid());
+            set.union(knights::entity!(a, {
+                name: (&format!("Lover {}", i)[..]).try_into().unwrap(),
+                loves: b
+            }));
+            set.union(knights::entity!(b, {
+                name: (&format!("Beloved {}", i)[..]).try_into().unwrap(),
+                loves: a
+            }));
+            couples.push((a, b));
+        }
+        (set, couples)
+    }
+
+    #[test]
+    fn matches_reference() {
+        let (set, couples) = couples(50);
+        let reference = Reference {
+            set: set.clone(),
+            loads: Cell::new(0),
+            scans: Cell::new(0),
+        };
+        let partial = PartialSet::new(&reference, 10 * 2 * TRIBLE_LEN);
+
+        for &(a, b) in &couples {
+            let expected: Vec<_> = find!(
+                ctx,
+                (beloved, name),
+                knights::pattern!(ctx, set, [
+                    {(a) @ loves: beloved},
+                    {beloved @ name: name}])
+            )
+            .collect();
+            let found: Vec<_> = find!(
+                ctx,
+                (beloved, name),
+                knights::pattern!(ctx, partial, [
+                    {(a) @ loves: beloved},
+                    {beloved @ name: name}])
+            )
+            .collect();
+            assert_eq!(found, expected);
+            assert_eq!(found[0].as_ref().unwrap().0, b);
+            assert!(partial.cached_bytes() <= 10 * 2 * TRIBLE_LEN);
+        }
+        assert_eq!(reference.scans.get(), 0);
+        assert!(partial.stats().evictions > 0);
+
+        let all: Vec<_> = find!(
+            ctx,
+            (lover, name),
+            knights::pattern!(ctx, partial, [{lover @ name: name}])
+        )
+        .collect();
+        assert_eq!(all.len(), 100);
+        assert_eq!(reference.scans.get(), 1);
+    }
+
+    #[test]
+    fn repeated_access_is_cached() {
+        let (set, couples) = couples(3);
+        let reference = Reference {
+            set,
+            loads: Cell::new(0),
+            scans: Cell::new(0),
+        };
+        let partial = PartialSet::new(&reference, usize::MAX);
+
+        let (a, _) = couples[0];
+        let missing = ufoid();
+        for _ in 0..10 {
+            assert_eq!(partial.entity(&a).len(), 2);
+            assert_eq!(partial.entity(&missing).len(), 0);
+        }
+        assert_eq!(reference.loads.get(), 2);
+        assert_eq!(
+            partial.stats(),
+            PartialSetStats {
+                hits: 18,
+                misses: 2,
+                evictions: 0
+            }
+        );
+    }
+}
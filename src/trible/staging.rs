@@ -0,0 +1,157 @@
+//! A write-optimized buffer for building large [TribleSet]s.
+//!
+//! Ingestion usually writes all tribles first and only queries the result
+//! afterwards. A [StagingSet] only appends to a flat buffer while tribles
+//! arrive. Duplicates are kept until the set is finalized, which sorts and
+//! deduplicates the buffer once and then builds each index bottom-up from
+//! its own sort order with [PATCH::from_entries].
+
+use std::collections::HashSet;
+
+use crate::patch::{Entry, PATCH};
+use crate::trible::{Trible, TRIBLE_LEN};
+use crate::tribleset::TribleIndices;
+use crate::TribleSet;
+
+#[derive(Debug, Clone, Default)]
+pub struct StagingSet {
+    tribles: Vec<[u8; TRIBLE_LEN]>,
+    /// The distinct tribles of the first `seen_len` buffered ones, only
+    /// maintained once [StagingSet::contains] is used.
+    seen: HashSet<[u8; TRIBLE_LEN]>,
+    seen_len: usize,
+}
+
+impl StagingSet {
+    pub fn new() -> Self {
+        StagingSet::default()
+    }
+
+    pub fn insert(&mut self, trible: &Trible) {
+        self.insert_raw(&trible.data)
+    }
+
+    pub fn insert_raw(&mut self, data: &[u8; TRIBLE_LEN]) {
+        self.tribles.push(*data);
+    }
+
+    /// The number of staged tribles, including duplicates.
+    pub fn len(&self) -> usize {
+        self.tribles.len()
+    }
+
+    /// Checks whether `trible` has been staged, e.g. to skip duplicates
+    /// while importing.
+    ///
+    /// The tribles are only hashed when this is called, so inserting stays
+    /// a plain append for importers that never check, and each trible is
+    /// hashed once no matter how often this is called.
+    pub fn contains(&mut self, trible: &Trible) -> bool {
+        self.seen.extend(&self.tribles[self.seen_len..]);
+        self.seen_len = self.tribles.len();
+        self.seen.contains(&trible.data)
+    }
+
+    /// Builds the indexed [TribleSet] from the staged tribles.
+    ///
+    /// The indices share their leaves and are built in parallel.
+    pub fn finalize(self) -> TribleSet {
+        drop(self.seen);
+        let mut tribles = self.tribles;
+        tribles.sort_unstable();
+        tribles.dedup();
+        let entries: Vec<Entry<TRIBLE_LEN>> = tribles.iter().map(Entry::new).collect();
+        drop(tribles);
+
+        let mut indices = TribleIndices::EMPTY;
+        let TribleIndices {
+            eav,
+            vea,
+            ave,
+            vae,
+            eva,
+            aev,
+        } = &mut indices;
+        rayon::scope(|s| {
+            let entries = &entries;
+            s.spawn(move |_| *eav = PATCH::from_entries(entries));
+            s.spawn(move |_| *eva = PATCH::from_entries(entries));
+            s.spawn(move |_| *aev = PATCH::from_entries(entries));
+            s.spawn(move |_| *ave = PATCH::from_entries(entries));
+            s.spawn(move |_| *vea = PATCH::from_entries(entries));
+            s.spawn(move |_| *vae = PATCH::from_entries(entries));
+        });
+        TribleSet::from(indices)
+    }
+}
+
+impl Extend<Trible> for StagingSet {
+    fn extend<I: IntoIterator<Item = Trible>>(&mut self, iter: I) {
+        for t in iter {
+            self.insert(&t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn finalize_equals_incremental(
+            entries in prop::collection::vec(prop::collection::vec(0u8..4, 64), 1..256)
+        ) {
+            let tribles: Vec<Trible> = entries
+                .iter()
+                .map(|entry| {
+                    let mut data = [0; TRIBLE_LEN];
+                    data.copy_from_slice(&entry[..]);
+                    Trible { data }
+                })
+                .collect();
+
+            let mut staging = StagingSet::new();
+            staging.extend(tribles.iter().cloned());
+            let mut incremental = TribleSet::new();
+            for t in &tribles {
+                incremental.insert(t);
+            }
+
+            prop_assert_eq!(staging.len(), tribles.len());
+            let set = staging.finalize();
+            prop_assert_eq!(set.len(), incremental.len());
+            let (set, incremental) = (set.indices(), incremental.indices());
+            prop_assert_eq!(&set.eav, &incremental.eav);
+            prop_assert_eq!(&set.eva, &incremental.eva);
+            prop_assert_eq!(&set.aev, &incremental.aev);
+            prop_assert_eq!(&set.ave, &incremental.ave);
+            prop_assert_eq!(&set.vea, &incremental.vea);
+            prop_assert_eq!(&set.vae, &incremental.vae);
+        }
+    }
+
+    #[test]
+    fn contains() {
+        let trible = |i: u8| Trible {
+            data: [i; TRIBLE_LEN],
+        };
+        let mut staging = StagingSet::new();
+        assert!(!staging.contains(&trible(1)));
+
+        staging.insert(&trible(1));
+        staging.insert(&trible(2));
+        assert!(staging.contains(&trible(1)));
+        assert!(staging.contains(&trible(2)));
+        assert!(!staging.contains(&trible(3)));
+
+        // Tribles staged after a check are found by the next one.
+        staging.insert(&trible(3));
+        staging.insert(&trible(1));
+        assert!(staging.contains(&trible(3)));
+        assert!(!staging.contains(&trible(4)));
+        assert_eq!(staging.len(), 4);
+        assert_eq!(staging.finalize().len(), 3);
+    }
+}
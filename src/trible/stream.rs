@@ -0,0 +1,224 @@
+//! Streaming operations over EAV ordered trible sequences.
+//!
+//! A [TribleSet](crate::TribleSet) has to hold all of its tribles in memory,
+//! which makes it unsuitable for compacting datasets that are larger than RAM.
+//! The functions in this module instead operate on iterators that yield
+//! raw tribles in ascending EAV order, as produced by
+//! [TribleSet::iter_ordered](crate::TribleSet::iter_ordered),
+//! [SimpleArchive::iter] and, for archives over an
+//! [OrderedUniverse](crate::triblearchive::succinctarchive::OrderedUniverse),
+//! [SuccinctArchive::iter_raw](crate::triblearchive::SuccinctArchive::iter_raw).
+//!
+//! [union_streams] and [write_union] only keep the current head of every
+//! input in memory, so merging `k` streams needs `O(k)` space, independent
+//! of their length. [merge_archives] additionally holds the merged archive,
+//! which is `O(n)` in the number of distinct tribles; use [write_union] to
+//! write the result to a file instead.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, Write};
+
+use digest::{typenum::U32, Digest};
+
+use crate::blobset::BlobSet;
+use crate::trible::TRIBLE_LEN;
+use crate::triblearchive::SimpleArchive;
+use crate::Handle;
+
+/// Merges EAV ordered trible streams into a single EAV ordered stream,
+/// calling `out` once for every distinct trible.
+///
+/// Inputs must be sorted in ascending order, but may contain duplicates
+/// within and across streams.
+pub fn union_streams<I>(mut inputs: Vec<I>, mut out: impl FnMut(&[u8; TRIBLE_LEN]))
+where
+    I: Iterator<Item = [u8; TRIBLE_LEN]>,
+{
+    let mut heads = BinaryHeap::with_capacity(inputs.len());
+    for (i, input) in inputs.iter_mut().enumerate() {
+        if let Some(trible) = input.next() {
+            heads.push(Reverse((trible, i)));
+        }
+    }
+
+    let mut prev: Option<[u8; TRIBLE_LEN]> = None;
+    while let Some(Reverse((trible, i))) = heads.pop() {
+        if prev != Some(trible) {
+            debug_assert!(
+                prev.map_or(true, |p| p < trible),
+                "union_streams inputs must be EAV ordered"
+            );
+            out(&trible);
+            prev = Some(trible);
+        }
+        if let Some(next) = inputs[i].next() {
+            heads.push(Reverse((next, i)));
+        }
+    }
+}
+
+/// Merges EAV ordered trible streams and writes the distinct tribles to
+/// `writer` in the [SimpleArchive] format.
+///
+/// Stops at the first write error and returns it.
+pub fn write_union<I, W>(inputs: Vec<I>, writer: &mut W) -> io::Result<()>
+where
+    I: Iterator<Item = [u8; TRIBLE_LEN]>,
+    W: Write,
+{
+    let mut result = Ok(());
+    union_streams(inputs, |t| {
+        if result.is_ok() {
+            result = writer.write_all(t);
+        }
+    });
+    result
+}
+
+/// Merges the given archives into a single archive, which is stored in
+/// `store`, without building an intermediate [TribleSet](crate::TribleSet).
+///
+/// The merged archive is assembled in memory before it is stored.
+pub fn merge_archives<'a, H>(
+    archives: impl IntoIterator<Item = &'a SimpleArchive>,
+    store: &mut BlobSet<H>,
+) -> Handle<H, SimpleArchive>
+where
+    H: Digest<OutputSize = U32>,
+{
+    let streams: Vec<_> = archives.into_iter().map(|a| a.iter()).collect();
+    let mut tribles: Vec<[u8; TRIBLE_LEN]> = Vec::new();
+    union_streams(streams, |t| tribles.push(*t));
+    store.put(SimpleArchive::from_sorted(tribles))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trible::Trible;
+    use crate::triblearchive::succinctarchive::OrderedUniverse;
+    use crate::triblearchive::SuccinctArchive;
+    use crate::types::hash::Blake3;
+    use crate::TribleSet;
+    use proptest::prelude::*;
+    use sucds::bit_vectors::Rank9Sel;
+
+    fn arb_trible() -> impl Strategy<Value = [u8; TRIBLE_LEN]> {
+        (1u8..4, 1u8..4, 0u8..4).prop_map(|(e, a, v)| {
+            let mut data = [0; TRIBLE_LEN];
+            data[15] = e;
+            data[31] = a;
+            data[63] = v;
+            data
+        })
+    }
+
+    fn arb_set() -> impl Strategy<Value = TribleSet> {
+        prop::collection::vec(arb_trible(), 0..32)
+            .prop_map(|ts| ts.iter().map(|&data| Trible { data }).collect())
+    }
+
+    #[test]
+    fn union_streams_dedups_overlap() {
+        let a = vec![[1; TRIBLE_LEN], [2; TRIBLE_LEN], [3; TRIBLE_LEN]];
+        let b = vec![[2; TRIBLE_LEN], [4; TRIBLE_LEN]];
+        let c = vec![[1; TRIBLE_LEN], [1; TRIBLE_LEN], [4; TRIBLE_LEN]];
+
+        let mut merged = vec![];
+        union_streams(vec![a.into_iter(), b.into_iter(), c.into_iter()], |t| {
+            merged.push(*t)
+        });
+
+        assert_eq!(
+            merged,
+            vec![
+                [1; TRIBLE_LEN],
+                [2; TRIBLE_LEN],
+                [3; TRIBLE_LEN],
+                [4; TRIBLE_LEN]
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn union_streams_matches_union(sets in prop::collection::vec(arb_set(), 0..8)) {
+            let mut expected = TribleSet::new();
+            for set in &sets {
+                expected.union(set.clone());
+            }
+
+            let mut merged = TribleSet::new();
+            union_streams(
+                sets.iter().map(|s| s.iter_ordered()).collect(),
+                |t| merged.insert_raw(t),
+            );
+
+            prop_assert_eq!(merged, expected);
+        }
+
+        #[test]
+        fn merge_archives_matches_union(sets in prop::collection::vec(arb_set(), 0..8)) {
+            let mut expected = TribleSet::new();
+            for set in &sets {
+                expected.union(set.clone());
+            }
+
+            let archives: Vec<SimpleArchive> = sets.iter().map(|s| s.into()).collect();
+            let mut store: BlobSet<Blake3> = BlobSet::new();
+            let handle = merge_archives(&archives, &mut store);
+            let merged: TribleSet = (&store.get(handle).unwrap().unwrap()).into();
+
+            prop_assert_eq!(merged, expected);
+        }
+
+        #[test]
+        fn write_union_matches_merge_archives(sets in prop::collection::vec(arb_set(), 0..8)) {
+            let archives: Vec<SimpleArchive> = sets.iter().map(|s| s.into()).collect();
+            let mut store: BlobSet<Blake3> = BlobSet::new();
+            let handle = merge_archives(&archives, &mut store);
+
+            let mut written = Vec::new();
+            write_union(archives.iter().map(|a| a.iter()).collect(), &mut written).unwrap();
+
+            prop_assert_eq!(&written[..], &store.get_raw(handle.hash).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn succinct_archives_merge() {
+        let a: TribleSet = (1u8..4)
+            .map(|e| {
+                let mut data = [0; TRIBLE_LEN];
+                data[15] = e;
+                data[31] = 1;
+                data[63] = e;
+                Trible { data }
+            })
+            .collect();
+        let b: TribleSet = (3u8..6)
+            .map(|e| {
+                let mut data = [0; TRIBLE_LEN];
+                data[15] = e;
+                data[31] = 1;
+                data[63] = e;
+                Trible { data }
+            })
+            .collect();
+        let mut expected = a.clone();
+        expected.union(b.clone());
+
+        let archive: SuccinctArchive<OrderedUniverse, Rank9Sel> = (&a).into();
+        let mut merged = TribleSet::new();
+        union_streams(
+            vec![
+                Box::new(archive.iter_raw()) as Box<dyn Iterator<Item = [u8; TRIBLE_LEN]>>,
+                Box::new(b.iter_ordered()),
+            ],
+            |t| merged.insert_raw(t),
+        );
+
+        assert_eq!(merged, expected);
+    }
+}
@@ -1,5 +1,7 @@
+pub mod builder;
 pub mod simplearchive;
 pub mod succinctarchive;
 
+pub use builder::ArchiveBuilder;
 pub use simplearchive::SimpleArchive;
 pub use succinctarchive::SuccinctArchive;
@@ -0,0 +1,366 @@
+//! Building [SimpleArchive]s from more tribles than fit into memory.
+//!
+//! An [ArchiveBuilder] collects tribles in a fixed size buffer. Whenever
+//! the buffer is full it is sorted, deduplicated and written to a run file
+//! in a temporary directory. [ArchiveBuilder::finish] merges the runs and
+//! the remaining buffer with [union_streams], so apart from the final
+//! archive at most one buffer of tribles is held in memory.
+//! [ArchiveBuilder::finish_to] writes the merged archive with
+//! [write_union] instead, so that not even the archive has to fit into
+//! memory.
+//!
+//! Run files are removed when the builder is finished or dropped, also
+//! when an error occurred.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use digest::{typenum::U32, Digest};
+
+use crate::blobset::BlobSet;
+use crate::trible::stream::{union_streams, write_union};
+use crate::trible::{Trible, TRIBLE_LEN};
+use crate::{ufoid, Handle};
+
+use super::SimpleArchive;
+
+/// The default number of tribles that are buffered before a run is
+/// written, which amounts to 64MiB.
+pub const DEFAULT_BUFFER_LEN: usize = 1 << 20;
+
+pub struct ArchiveBuilder {
+    buffer: Vec<[u8; TRIBLE_LEN]>,
+    buffer_len: usize,
+    dir: PathBuf,
+    runs: Vec<PathBuf>,
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        ArchiveBuilder::with_buffer_len(DEFAULT_BUFFER_LEN)
+    }
+
+    pub fn with_buffer_len(buffer_len: usize) -> Self {
+        assert!(buffer_len > 0);
+        ArchiveBuilder {
+            buffer: Vec::new(),
+            buffer_len,
+            dir: std::env::temp_dir(),
+            runs: Vec::new(),
+        }
+    }
+
+    /// Writes run files to `dir` instead of the system's temporary
+    /// directory.
+    pub fn spill_to(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    pub fn add(&mut self, trible: &Trible) -> io::Result<()> {
+        self.add_raw(&trible.data)
+    }
+
+    pub fn add_raw(&mut self, data: &[u8; TRIBLE_LEN]) -> io::Result<()> {
+        self.buffer.push(*data);
+        if self.buffer.len() >= self.buffer_len {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// The number of run files written so far.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.buffer.sort_unstable();
+        self.buffer.dedup();
+
+        let path = self
+            .dir
+            .join(format!("tribles-run-{}.tmp", hex::encode(ufoid())));
+        let file = File::create(&path)?;
+        self.runs.push(path);
+
+        let mut writer = BufWriter::new(file);
+        for trible in &self.buffer {
+            writer.write_all(trible)?;
+        }
+        writer.flush()?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Merges all added tribles into a single archive stored in `store`.
+    ///
+    /// The archive is assembled in memory before it is stored.
+    pub fn finish<H>(mut self, store: &mut BlobSet<H>) -> io::Result<Handle<H, SimpleArchive>>
+    where
+        H: Digest<OutputSize = U32>,
+    {
+        if self.runs.is_empty() {
+            let mut buffer = std::mem::take(&mut self.buffer);
+            buffer.sort_unstable();
+            buffer.dedup();
+            return Ok(store.put(SimpleArchive::from_sorted(buffer)));
+        }
+
+        let (streams, error) = self.streams()?;
+        let mut tribles = Vec::new();
+        union_streams(streams, |t| tribles.push(*t));
+        if let Some(err) = error.borrow_mut().take() {
+            return Err(err);
+        }
+        Ok(store.put(SimpleArchive::from_sorted(tribles)))
+    }
+
+    /// Merges all added tribles and writes them to `writer` in the
+    /// [SimpleArchive] format, holding at most one buffer of tribles in
+    /// memory.
+    pub fn finish_to<W: Write>(mut self, writer: &mut W) -> io::Result<()> {
+        let (streams, error) = self.streams()?;
+        write_union(streams, writer)?;
+        if let Some(err) = error.borrow_mut().take() {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// The sorted buffer and a reader for every run, together with the slot
+    /// in which the readers record their first error.
+    fn streams(
+        &mut self,
+    ) -> io::Result<(Vec<Box<dyn Iterator<Item = [u8; TRIBLE_LEN]>>>, RunError)> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_unstable();
+
+        let error = Rc::new(RefCell::new(None));
+        let mut streams: Vec<Box<dyn Iterator<Item = [u8; TRIBLE_LEN]>>> =
+            vec![Box::new(buffer.into_iter())];
+        for path in &self.runs {
+            streams.push(Box::new(RunReader {
+                reader: BufReader::new(File::open(path)?),
+                error: error.clone(),
+            }));
+        }
+        Ok((streams, error))
+    }
+}
+
+impl Default for ArchiveBuilder {
+    fn default() -> Self {
+        ArchiveBuilder::new()
+    }
+}
+
+impl Drop for ArchiveBuilder {
+    fn drop(&mut self) {
+        for path in &self.runs {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+type RunError = Rc<RefCell<Option<io::Error>>>;
+
+/// Reads the tribles of a run file, ending the stream early and recording
+/// the error if the file can't be read.
+struct RunReader {
+    reader: BufReader<File>,
+    error: RunError,
+}
+
+impl Iterator for RunReader {
+    type Item = [u8; TRIBLE_LEN];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut trible = [0; TRIBLE_LEN];
+        let mut read = 0;
+        while read < TRIBLE_LEN {
+            match self.reader.read(&mut trible[read..]) {
+                Ok(0) if read == 0 => return None,
+                Ok(0) => {
+                    *self.error.borrow_mut() = Some(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "truncated run file",
+                    ));
+                    return None;
+                }
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    *self.error.borrow_mut() = Some(e);
+                    return None;
+                }
+            }
+        }
+        Some(trible)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::hash::Blake3;
+    use crate::{assert_sets_equal, Bloblike, TribleSet};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, hex::encode(ufoid())));
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &PathBuf) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn spilled_matches_in_memory() {
+        let dir = scratch_dir("spilled_matches_in_memory");
+        let mut builder = ArchiveBuilder::with_buffer_len(1000).spill_to(&dir);
+        let mut expected = TribleSet::new();
+        for i in 0..20000u32 {
+            // Every trible is added twice, in different runs.
+            let n = i % 10000;
+            let mut data = [0; TRIBLE_LEN];
+            data[12..16].copy_from_slice(&(n + 1).to_be_bytes());
+            data[31] = 1;
+            data[60..64].copy_from_slice(&n.wrapping_mul(7919).to_be_bytes());
+            builder.add_raw(&data).unwrap();
+            expected.insert_raw(&data);
+        }
+        assert_eq!(builder.runs(), 20);
+        assert_eq!(entries(&dir), 20);
+
+        let mut store: BlobSet<Blake3> = BlobSet::new();
+        let handle = builder.finish(&mut store).unwrap();
+        let archive = store.get(handle).unwrap().unwrap();
+        assert_eq!(archive.iter().count(), 10000);
+        assert_sets_equal!(TribleSet::from(&archive), expected);
+        assert_eq!(entries(&dir), 0);
+
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_memory_only() {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_raw(&[2; TRIBLE_LEN]).unwrap();
+        builder.add_raw(&[1; TRIBLE_LEN]).unwrap();
+        builder.add_raw(&[2; TRIBLE_LEN]).unwrap();
+        assert_eq!(builder.runs(), 0);
+
+        let mut store: BlobSet<Blake3> = BlobSet::new();
+        let handle = builder.finish(&mut store).unwrap();
+        let archive = store.get(handle).unwrap().unwrap();
+        assert_eq!(
+            archive.iter().collect::<Vec<_>>(),
+            vec![[1; TRIBLE_LEN], [2; TRIBLE_LEN]]
+        );
+    }
+
+    /// Checks that the written tribles are exactly `0..len` as encoded by
+    /// [sequential], without keeping them.
+    struct ExpectSequential {
+        next: u32,
+        len: u32,
+        partial: Vec<u8>,
+    }
+
+    impl Write for ExpectSequential {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.partial.extend_from_slice(buf);
+            let complete = self.partial.len() - self.partial.len() % TRIBLE_LEN;
+            for trible in self.partial[..complete].chunks(TRIBLE_LEN) {
+                assert!(self.next < self.len);
+                assert_eq!(trible, &sequential(self.next)[..]);
+                self.next += 1;
+            }
+            self.partial.drain(..complete);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Tribles that sort in the order of `n`.
+    fn sequential(n: u32) -> [u8; TRIBLE_LEN] {
+        let mut data = [0; TRIBLE_LEN];
+        data[12..16].copy_from_slice(&(n + 1).to_be_bytes());
+        data[31] = 1;
+        data[60..64].copy_from_slice(&n.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn finish_to_streams_large_imports() {
+        const LEN: u32 = 1 << 21;
+        let dir = scratch_dir("finish_to_streams_large_imports");
+        let mut builder = ArchiveBuilder::with_buffer_len(1 << 16).spill_to(&dir);
+        for i in 0..2 * LEN {
+            // Every trible is added twice, in shuffled order.
+            builder
+                .add_raw(&sequential(i.wrapping_mul(7919) % LEN))
+                .unwrap();
+        }
+        assert_eq!(builder.runs(), 64);
+
+        let mut writer = ExpectSequential {
+            next: 0,
+            len: LEN,
+            partial: Vec::new(),
+        };
+        builder.finish_to(&mut writer).unwrap();
+        assert_eq!(writer.next, LEN);
+        assert!(writer.partial.is_empty());
+        assert_eq!(entries(&dir), 0);
+
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn finish_to_matches_finish() {
+        let mut builder = ArchiveBuilder::with_buffer_len(3);
+        let mut expected = ArchiveBuilder::with_buffer_len(3);
+        for i in 0..20u8 {
+            builder.add_raw(&[i % 7 + 1; TRIBLE_LEN]).unwrap();
+            expected.add_raw(&[i % 7 + 1; TRIBLE_LEN]).unwrap();
+        }
+        let mut written = Vec::new();
+        builder.finish_to(&mut written).unwrap();
+
+        let mut store: BlobSet<Blake3> = BlobSet::new();
+        let handle = expected.finish(&mut store).unwrap();
+        assert_eq!(
+            written,
+            store.get(handle).unwrap().unwrap().into_blob().to_vec()
+        );
+    }
+
+    #[test]
+    fn truncated_run_is_cleaned_up() {
+        let dir = scratch_dir("truncated_run_is_cleaned_up");
+        let mut builder = ArchiveBuilder::with_buffer_len(2).spill_to(&dir);
+        for i in 0..4u8 {
+            builder.add_raw(&[i; TRIBLE_LEN]).unwrap();
+        }
+        assert_eq!(entries(&dir), 2);
+
+        let run = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        fs::write(&run, &[0; TRIBLE_LEN + 1]).unwrap();
+
+        let mut store: BlobSet<Blake3> = BlobSet::new();
+        let err = builder.finish(&mut store).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(entries(&dir), 0);
+
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -10,6 +10,23 @@ use crate::{
 
 pub struct SimpleArchive(Bytes);
 
+impl SimpleArchive {
+    /// Creates an archive from tribles that are already sorted in
+    /// ascending EAV order and free of duplicates.
+    pub fn from_sorted(tribles: Vec<[u8; TRIBLE_LEN]>) -> Self {
+        debug_assert!(tribles.windows(2).all(|w| w[0] < w[1]));
+        let buffer: Vec<u8> = bytemuck::allocation::cast_vec(tribles);
+        SimpleArchive(buffer.into())
+    }
+
+    /// Iterates over the archived tribles in ascending EAV order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = [u8; TRIBLE_LEN]> + 'a {
+        self.0
+            .chunks_exact(TRIBLE_LEN)
+            .map(|t| t.try_into().unwrap())
+    }
+}
+
 impl Bloblike for SimpleArchive {
     fn from_blob(blob: Bytes) -> Result<Self, BlobParseError> {
         let len: usize = blob.len();
@@ -66,17 +83,16 @@ impl Bloblike for SimpleArchive {
 impl From<&TribleSet> for SimpleArchive {
     fn from(set: &TribleSet) -> Self {
         let mut tribles: Vec<[u8; 64]> = Vec::with_capacity(set.len());
-        tribles.extend(set.eav.iter_prefix::<64>().map(|p| p.0));
-        let buffer: Vec<u8> = bytemuck::allocation::cast_vec(tribles);
-        SimpleArchive(buffer.into())
+        tribles.extend(set.iter_ordered());
+        SimpleArchive::from_sorted(tribles)
     }
 }
 
 impl From<&SimpleArchive> for TribleSet {
     fn from(archive: &SimpleArchive) -> Self {
         let mut tribles = TribleSet::new();
-        for t in archive.0.chunks_exact(TRIBLE_LEN) {
-            tribles.insert_raw(t.try_into().unwrap());
+        for t in archive.iter() {
+            tribles.insert_raw(&t);
         }
         tribles
     }
@@ -6,7 +6,7 @@ use std::iter;
 use succinctarchiveconstraint::*;
 
 use crate::query::TriblePattern;
-use crate::trible::Trible;
+use crate::trible::{Trible, TRIBLE_LEN};
 use crate::{id_into_value, Id, Valuelike};
 use crate::{Bloblike, Value};
 
@@ -61,6 +61,14 @@ where
             t
         })
     }
+
+    /// Iterates over the raw tribles, e.g. to merge them with
+    /// [union_streams](crate::trible::stream::union_streams).
+    ///
+    /// The tribles are only in EAV order if the universe is ordered.
+    pub fn iter_raw<'a>(&'a self) -> impl Iterator<Item = [u8; TRIBLE_LEN]> + 'a {
+        self.iter().map(|t| t.data)
+    }
 }
 
 impl<U, B> From<&TribleSet> for SuccinctArchive<U, B>
@@ -69,6 +77,7 @@ where
     B: Build + Access + Rank + Select + NumBits,
 {
     fn from(set: &TribleSet) -> Self {
+        let set = set.indices();
         let triple_count = set.eav.len() as usize;
         assert!(triple_count > 0);
 
@@ -276,7 +285,7 @@ where
 mod tests {
     use std::convert::TryInto;
 
-    use crate::{find, trible::Trible, types::ShortString, ufoid, Id, NS};
+    use crate::{assert_sets_equal, find, trible::Trible, types::ShortString, ufoid, Id, NS};
 
     use super::*;
     use itertools::Itertools;
@@ -317,7 +326,7 @@ mod tests {
             let archive: SuccinctArchive::<CompressedUniverse<DacsOpt>, Rank9Sel> = (&set).into();
             let set_: TribleSet = (&archive).into();
 
-            assert_eq!(set, set_);
+            assert_sets_equal!(set, set_);
         }
 
         #[test]
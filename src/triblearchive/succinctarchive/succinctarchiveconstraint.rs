@@ -468,4 +468,11 @@ where
             _ => panic!("invalid trible constraint state"),
         }
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "succinct archive {} {} {}",
+            self.variable_e, self.variable_a, self.variable_v
+        ))
+    }
 }
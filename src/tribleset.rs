@@ -1,19 +1,41 @@
+mod entityview;
+pub mod expiry;
 mod triblesetconstraint;
 
+pub use entityview::EntityView;
+
 use triblesetconstraint::*;
 
 use crate::query::TriblePattern;
 
-use crate::patch::{Entry, PATCH};
+use crate::patch::{key_hash, Entry, PATCH};
 use crate::trible::{
-    AEVOrder, AVEOrder, EAVOrder, EVAOrder, Trible, TribleSegmentation, VAEOrder, VEAOrder,
-    TRIBLE_LEN,
+    AEVOrder, AVEOrder, EAVOrder, EVAOrder, Trible, TribleSegmentation, VAEOrder, VEAOrder, A_END,
+    A_START, E_END, E_START, TRIBLE_LEN, V_END, V_START,
 };
-use crate::{Id, Value, Valuelike};
+use crate::{Id, Value, Valuelike, ID_LEN};
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
+use std::ops::{BitAnd, Sub};
+
+/// The number of tribles up to which a [TribleSet] is stored as a sorted
+/// array instead of being indexed.
+///
+/// Most sets are small, e.g. the fragments built by `entity!` or the
+/// deltas of a single change, and for those the six indices cost more
+/// than they are worth: every trible needs a leaf and every index its own
+/// branches, while a small set needs a single allocation and answers
+/// lookups by binary search. Past a handful of tribles the indices win,
+/// because queries on a small set have to build them first. The
+/// `tribleset/small` benchmarks compare both sides of the threshold.
+pub const SMALL_SET_LEN: usize = 8;
 
+/// The six indices of an indexed [TribleSet], one for each ordering of
+/// entity, attribute and value. All indices share the same leaves.
 #[derive(Debug, Clone)]
-pub struct TribleSet {
+pub struct TribleIndices {
     pub eav: PATCH<64, EAVOrder, TribleSegmentation>,
     pub vea: PATCH<64, VEAOrder, TribleSegmentation>,
     pub ave: PATCH<64, AVEOrder, TribleSegmentation>,
@@ -22,8 +44,61 @@ pub struct TribleSet {
     pub aev: PATCH<64, AEVOrder, TribleSegmentation>,
 }
 
-impl TribleSet {
-    pub fn union(&mut self, other: Self) {
+impl TribleIndices {
+    pub const EMPTY: TribleIndices = TribleIndices {
+        eav: PATCH::new(),
+        eva: PATCH::new(),
+        aev: PATCH::new(),
+        ave: PATCH::new(),
+        vea: PATCH::new(),
+        vae: PATCH::new(),
+    };
+
+    fn from_tribles(tribles: &[[u8; TRIBLE_LEN]]) -> TribleIndices {
+        let entries: Vec<Entry<TRIBLE_LEN>> = tribles.iter().map(Entry::new).collect();
+        TribleIndices {
+            eav: PATCH::from_entries(&entries),
+            eva: PATCH::from_entries(&entries),
+            aev: PATCH::from_entries(&entries),
+            ave: PATCH::from_entries(&entries),
+            vea: PATCH::from_entries(&entries),
+            vae: PATCH::from_entries(&entries),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.eav.len() as usize
+    }
+
+    /// The tribles of attribute `a`, ordered by entity and value.
+    pub(crate) fn attribute_tribles(&self, a: &Id) -> Vec<[u8; TRIBLE_LEN]> {
+        let mut tribles = Vec::new();
+        self.aev.infixes(a, |e: Id| {
+            let mut prefix = [0; 2 * ID_LEN];
+            prefix[..ID_LEN].copy_from_slice(a);
+            prefix[ID_LEN..].copy_from_slice(&e);
+            self.aev.infixes(&prefix, |v: Value| {
+                let mut trible = [0; TRIBLE_LEN];
+                trible[E_START..=E_END].copy_from_slice(&e);
+                trible[A_START..=A_END].copy_from_slice(a);
+                trible[V_START..=V_END].copy_from_slice(&v);
+                tribles.push(trible);
+            });
+        });
+        tribles
+    }
+
+    fn insert_raw(&mut self, data: &[u8; TRIBLE_LEN]) {
+        let key = Entry::new(data);
+        self.eav.insert(&key);
+        self.eva.insert(&key);
+        self.aev.insert(&key);
+        self.ave.insert(&key);
+        self.vea.insert(&key);
+        self.vae.insert(&key);
+    }
+
+    fn union(&mut self, other: Self) {
         self.eav.union(other.eav);
         self.eva.union(other.eva);
         self.aev.union(other.aev);
@@ -32,19 +107,250 @@ impl TribleSet {
         self.vae.union(other.vae);
     }
 
-    pub fn new() -> TribleSet {
+    fn par_union(&mut self, other: Self) {
+        let TribleIndices {
+            eav,
+            vea,
+            ave,
+            vae,
+            eva,
+            aev,
+        } = self;
+        let TribleIndices {
+            eav: other_eav,
+            vea: other_vea,
+            ave: other_ave,
+            vae: other_vae,
+            eva: other_eva,
+            aev: other_aev,
+        } = other;
+        rayon::scope(|s| {
+            s.spawn(move |_| eav.par_union(other_eav));
+            s.spawn(move |_| eva.par_union(other_eva));
+            s.spawn(move |_| aev.par_union(other_aev));
+            s.spawn(move |_| ave.par_union(other_ave));
+            s.spawn(move |_| vea.par_union(other_vea));
+            s.spawn(move |_| vae.par_union(other_vae));
+        });
+    }
+}
+
+/// The counts of a single attribute, see [TribleSet::attribute_stats].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributeStats {
+    /// The number of distinct entities with the attribute.
+    pub entities: u64,
+    /// The number of distinct values of the attribute.
+    pub values: u64,
+    /// The number of tribles with the attribute.
+    pub tribles: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Repr {
+    /// At most [SMALL_SET_LEN] distinct tribles in ascending EAV order.
+    Small(Vec<[u8; TRIBLE_LEN]>),
+    Indexed(TribleIndices),
+}
+
+impl Default for Repr {
+    fn default() -> Self {
+        Repr::Small(Vec::new())
+    }
+}
+
+impl Repr {
+    /// Merges two small sets, indexing the result if it gets too large.
+    fn merge(mut tribles: Vec<[u8; TRIBLE_LEN]>, other: Vec<[u8; TRIBLE_LEN]>) -> Repr {
+        tribles.extend(other);
+        tribles.sort_unstable();
+        tribles.dedup();
+        if tribles.len() <= SMALL_SET_LEN {
+            Repr::Small(tribles)
+        } else {
+            Repr::Indexed(TribleIndices::from_tribles(&tribles))
+        }
+    }
+}
+
+/// A set of tribles.
+///
+/// Sets of up to [SMALL_SET_LEN] tribles are kept in a sorted array and
+/// are converted to [TribleIndices] once they grow past it. Sets that have
+/// been indexed stay indexed. The representation is not observable apart
+/// from performance, all operations work on and across both.
+#[derive(Debug, Clone)]
+pub struct TribleSet {
+    repr: Repr,
+}
+
+impl TribleSet {
+    pub fn union(&mut self, other: Self) {
+        self.repr = match (std::mem::take(&mut self.repr), other.repr) {
+            (Repr::Indexed(mut indices), Repr::Indexed(other)) => {
+                indices.union(other);
+                Repr::Indexed(indices)
+            }
+            (Repr::Indexed(mut indices), Repr::Small(tribles))
+            | (Repr::Small(tribles), Repr::Indexed(mut indices)) => {
+                tribles.iter().for_each(|data| indices.insert_raw(data));
+                Repr::Indexed(indices)
+            }
+            (Repr::Small(tribles), Repr::Small(other)) => Repr::merge(tribles, other),
+        };
+    }
+
+    /// Like [TribleSet::union], but merges the indices in parallel and
+    /// each index with [PATCH::par_union].
+    pub fn par_union(&mut self, other: Self) {
+        match (&mut self.repr, other.repr) {
+            (Repr::Indexed(indices), Repr::Indexed(other)) => indices.par_union(other),
+            (_, repr) => self.union(TribleSet { repr }),
+        }
+    }
+
+    /// The empty set. It has no index roots and can be used, e.g. for
+    /// unions and queries, without initializing the PATCH hash key.
+    pub const EMPTY: TribleSet = TribleSet {
+        repr: Repr::Small(Vec::new()),
+    };
+
+    pub const fn new() -> TribleSet {
+        TribleSet::EMPTY
+    }
+
+    /// Creates a set that only contains `trible`.
+    pub fn singleton(trible: &Trible) -> TribleSet {
         TribleSet {
-            eav: PATCH::new(),
-            eva: PATCH::new(),
-            aev: PATCH::new(),
-            ave: PATCH::new(),
-            vea: PATCH::new(),
-            vae: PATCH::new(),
+            repr: Repr::Small(vec![trible.data]),
         }
     }
 
     pub fn len(&self) -> usize {
-        return self.eav.len() as usize;
+        match &self.repr {
+            Repr::Small(tribles) => tribles.len(),
+            Repr::Indexed(indices) => indices.len(),
+        }
+    }
+
+    /// Returns the indices of the set. They are borrowed for indexed sets
+    /// and built on demand for small ones, so callers should hold on to
+    /// them for the duration of an operation.
+    pub fn indices(&self) -> Cow<'_, TribleIndices> {
+        match &self.repr {
+            Repr::Small(tribles) => Cow::Owned(TribleIndices::from_tribles(tribles)),
+            Repr::Indexed(indices) => Cow::Borrowed(indices),
+        }
+    }
+
+    /// Iterates over all tribles in ascending EAV order.
+    pub fn iter_ordered<'a>(&'a self) -> impl Iterator<Item = [u8; TRIBLE_LEN]> + 'a {
+        let (small, indexed) = match &self.repr {
+            Repr::Small(tribles) => (&tribles[..], None),
+            Repr::Indexed(indices) => (&[][..], Some(indices)),
+        };
+        small.iter().copied().chain(
+            indexed
+                .into_iter()
+                .flat_map(|indices| indices.eav.iter_prefix::<TRIBLE_LEN>().map(|(t, _)| t)),
+        )
+    }
+
+    /// Returns a view of the attributes of entity `id`, or `None` if the
+    /// set contains no tribles about it.
+    pub fn entity(&self, id: Id) -> Option<EntityView> {
+        let found = match &self.repr {
+            Repr::Small(tribles) => tribles.iter().any(|t| t[0..ID_LEN] == id),
+            Repr::Indexed(indices) => indices.eav.has_prefix(&id),
+        };
+        if found {
+            Some(EntityView::new(self, id))
+        } else {
+            None
+        }
+    }
+
+    /// Counts the entities, values and tribles of `attr`.
+    ///
+    /// The counts are maintained by the indices, so this only costs a few
+    /// lookups regardless of the size of the set.
+    pub fn attribute_stats(&self, attr: &Id) -> AttributeStats {
+        let indices = self.indices();
+        AttributeStats {
+            entities: indices.aev.segmented_len(attr),
+            values: indices.ave.segmented_len(attr),
+            tribles: indices.aev.count_prefix(attr),
+        }
+    }
+
+    /// Iterates over all attributes in ascending order, together with the
+    /// number of tribles that have them.
+    pub fn attributes(&self) -> impl Iterator<Item = (Id, u64)> {
+        let attributes: Vec<_> = self.indices().aev.iter_prefix::<ID_LEN>().collect();
+        attributes.into_iter()
+    }
+
+    /// Calls `f` for every distinct infix following `prefix` in the EAV
+    /// ordering, in ascending order.
+    pub(crate) fn eav_infixes<const PREFIX_LEN: usize, const INFIX_LEN: usize, F>(
+        &self,
+        prefix: &[u8; PREFIX_LEN],
+        mut f: F,
+    ) where
+        F: FnMut([u8; INFIX_LEN]),
+    {
+        match &self.repr {
+            Repr::Small(tribles) => {
+                let mut last = None;
+                for t in tribles.iter().filter(|t| t[0..PREFIX_LEN] == prefix[..]) {
+                    let infix: [u8; INFIX_LEN] =
+                        t[PREFIX_LEN..PREFIX_LEN + INFIX_LEN].try_into().unwrap();
+                    if last != Some(infix) {
+                        f(infix);
+                        last = Some(infix);
+                    }
+                }
+            }
+            Repr::Indexed(indices) => indices.eav.infixes(prefix, f),
+        }
+    }
+
+    /// Returns the tribles that are contained in both sets.
+    pub fn intersection(&self, other: &TribleSet) -> TribleSet {
+        self.retained(|data| other.contains_raw(data))
+    }
+
+    /// Returns the tribles of this set that are not contained in `other`.
+    pub fn difference(&self, other: &TribleSet) -> TribleSet {
+        self.retained(|data| !other.contains_raw(data))
+    }
+
+    /// Keeps the same tribles in every index, so that they stay in sync.
+    fn retained<F>(&self, f: F) -> TribleSet
+    where
+        F: Fn(&[u8; TRIBLE_LEN]) -> bool,
+    {
+        let repr = match &self.repr {
+            Repr::Small(tribles) => Repr::Small(tribles.iter().filter(|t| f(t)).copied().collect()),
+            Repr::Indexed(indices) => {
+                let mut indices = indices.clone();
+                indices.eav.retain(&f);
+                indices.eva.retain(&f);
+                indices.aev.retain(&f);
+                indices.ave.retain(&f);
+                indices.vea.retain(&f);
+                indices.vae.retain(&f);
+                Repr::Indexed(indices)
+            }
+        };
+        TribleSet { repr }
+    }
+
+    pub(crate) fn contains_raw(&self, data: &[u8; TRIBLE_LEN]) -> bool {
+        match &self.repr {
+            Repr::Small(tribles) => tribles.binary_search(data).is_ok(),
+            Repr::Indexed(indices) => indices.eav.has_prefix(data),
+        }
     }
 
     pub fn insert(&mut self, trible: &Trible) {
@@ -52,24 +358,75 @@ impl TribleSet {
     }
 
     pub fn insert_raw(&mut self, data: &[u8; TRIBLE_LEN]) {
-        let key = Entry::new(data);
-        self.eav.insert(&key);
-        self.eva.insert(&key);
-        self.aev.insert(&key);
-        self.ave.insert(&key);
-        self.vea.insert(&key);
-        self.vae.insert(&key);
+        match &mut self.repr {
+            Repr::Small(tribles) => {
+                if let Err(i) = tribles.binary_search(data) {
+                    if tribles.len() < SMALL_SET_LEN {
+                        tribles.insert(i, *data);
+                    } else {
+                        let mut indices = TribleIndices::from_tribles(tribles);
+                        indices.insert_raw(data);
+                        self.repr = Repr::Indexed(indices);
+                    }
+                }
+            }
+            Repr::Indexed(indices) => indices.insert_raw(data),
+        }
+    }
+}
+
+impl From<TribleIndices> for TribleSet {
+    fn from(indices: TribleIndices) -> Self {
+        TribleSet {
+            repr: Repr::Indexed(indices),
+        }
     }
 }
 
 impl PartialEq for TribleSet {
     fn eq(&self, other: &Self) -> bool {
-        self.eav == other.eav
+        match (&self.repr, &other.repr) {
+            (Repr::Small(tribles), Repr::Small(other)) => tribles == other,
+            (Repr::Indexed(indices), Repr::Indexed(other)) => indices.eav == other.eav,
+            (Repr::Small(tribles), Repr::Indexed(indices))
+            | (Repr::Indexed(indices), Repr::Small(tribles)) => {
+                tribles.len() == indices.len()
+                    && tribles.iter().all(|data| indices.eav.has_prefix(data))
+            }
+        }
     }
 }
 
 impl Eq for TribleSet {}
 
+/// Hashes the set like the root of its indices, so that equal sets hash
+/// equally regardless of their representation.
+impl Hash for TribleSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let hash = match &self.repr {
+            Repr::Small(tribles) => tribles.iter().fold(0, |hash, data| hash ^ key_hash(data)),
+            Repr::Indexed(indices) => indices.eav.root_hash(),
+        };
+        state.write_u128(hash);
+    }
+}
+
+impl<'a> BitAnd for &'a TribleSet {
+    type Output = TribleSet;
+
+    fn bitand(self, other: Self) -> TribleSet {
+        self.intersection(other)
+    }
+}
+
+impl<'a> Sub for &'a TribleSet {
+    type Output = TribleSet;
+
+    fn sub(self, other: Self) -> TribleSet {
+        self.difference(other)
+    }
+}
+
 impl FromIterator<Trible> for TribleSet {
     fn from_iter<I: IntoIterator<Item = Trible>>(iter: I) -> Self {
         let mut set = TribleSet::new();
@@ -96,7 +453,7 @@ impl TriblePattern for TribleSet {
     where
         V: Valuelike,
     {
-        TribleSetConstraint::new(e, a, v, self)
+        TribleSetConstraint::new(e, a, v, self.indices())
     }
 }
 
@@ -104,7 +461,10 @@ impl TriblePattern for TribleSet {
 mod tests {
     use std::convert::TryInto;
 
-    use crate::{types::ShortString, ufoid, Id, NS};
+    use std::collections::{BTreeMap, HashSet};
+
+    use crate::patch::KeyOrdering;
+    use crate::{and, find, types::ShortString, ufoid, Id, NS};
 
     use super::*;
     use fake::{faker::name::raw::Name, locales::EN, Fake};
@@ -165,7 +525,327 @@ mod tests {
         assert_eq!(kb.len(), 4000000);
     }
 
+    #[test]
+    fn empty() {
+        // The hash key is process wide and other tests initialize it, so
+        // the check runs this test again in a fresh process.
+        if std::env::var_os("TRIBLES_EMPTY_PROBE").is_none() {
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "tribleset::tests::empty", "--test-threads", "1"])
+                .env("TRIBLES_EMPTY_PROBE", "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        let mut kb = TribleSet::EMPTY;
+        assert_eq!(kb.len(), 0);
+        assert_eq!(kb, TribleSet::new());
+        assert_eq!(
+            find!(ctx, (e, n), knights::pattern!(ctx, kb, [{e @ name: n}])).count(),
+            0
+        );
+
+        kb.union(TribleSet::EMPTY);
+        assert_eq!(kb.len(), 0);
+        assert!(!crate::patch::hash_key_initialized());
+    }
+
+    fn is_small(set: &TribleSet) -> bool {
+        matches!(set.repr, Repr::Small(_))
+    }
+
+    fn hash_of(set: &TribleSet) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        set.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The tribles of `n` knights in reverse EAV order.
+    fn knights(n: usize) -> Vec<Trible> {
+        (0..n)
+            .rev()
+            .map(|i| {
+                let mut e = [0; 16];
+                e[15] = i as u8 + 1;
+                let name: ShortString = (&format!("Knight {}", i)[..]).try_into().unwrap();
+                Trible::new(e, knights::ids::name, name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transitions() {
+        for n in [SMALL_SET_LEN - 1, SMALL_SET_LEN, SMALL_SET_LEN + 1] {
+            let tribles = knights(n);
+            let mut set = TribleSet::new();
+            for t in &tribles {
+                set.insert(t);
+                set.insert(t);
+            }
+            assert_eq!(is_small(&set), n <= SMALL_SET_LEN);
+            assert_eq!(set.len(), n);
+
+            let mut sorted: Vec<_> = tribles.iter().map(|t| t.data).collect();
+            sorted.sort();
+            assert_eq!(set.iter_ordered().collect::<Vec<_>>(), sorted);
+            assert!(tribles.iter().all(|t| set.contains_raw(&t.data)));
+
+            let indexed = TribleSet::from(TribleIndices::from_tribles(&sorted));
+            assert_eq!(set, indexed);
+            assert_eq!(indexed, set);
+            assert_eq!(hash_of(&set), hash_of(&indexed));
+
+            let names: HashSet<(Id, ShortString)> =
+                find!(ctx, (e, n), knights::pattern!(ctx, set, [{e @ name: n}]))
+                    .map(|r| r.unwrap())
+                    .collect();
+            assert_eq!(names.len(), n);
+
+            let knight = tribles[0].e();
+            let view = set.entity(knight).unwrap();
+            assert_eq!(
+                view.attributes().collect::<Vec<_>>(),
+                vec![knights::ids::name]
+            );
+            assert_eq!(view.values(knights::ids::name), vec![tribles[0].v()]);
+        }
+    }
+
+    #[test]
+    fn mixed_unions() {
+        let tribles = knights(3 * SMALL_SET_LEN);
+        let small = |range: std::ops::Range<usize>| -> TribleSet {
+            tribles[range].iter().copied().collect()
+        };
+        let indexed = |range: std::ops::Range<usize>| -> TribleSet {
+            let data: Vec<_> = tribles[range].iter().map(|t| t.data).collect();
+            TribleSet::from(TribleIndices::from_tribles(&data))
+        };
+        let all = indexed(0..3 * SMALL_SET_LEN);
+
+        let mut merged = small(0..SMALL_SET_LEN);
+        merged.union(small(SMALL_SET_LEN / 2..SMALL_SET_LEN + 1));
+        assert!(!is_small(&merged));
+        assert_eq!(merged, indexed(0..SMALL_SET_LEN + 1));
+
+        let mut merged = small(0..SMALL_SET_LEN / 2);
+        merged.union(small(SMALL_SET_LEN / 2..SMALL_SET_LEN));
+        assert!(is_small(&merged));
+        assert_eq!(merged, small(0..SMALL_SET_LEN));
+
+        let mut merged = small(0..SMALL_SET_LEN);
+        merged.union(indexed(SMALL_SET_LEN - 1..3 * SMALL_SET_LEN));
+        assert_eq!(merged, all);
+
+        let mut merged = indexed(0..2 * SMALL_SET_LEN + 1);
+        merged.union(small(2 * SMALL_SET_LEN..3 * SMALL_SET_LEN));
+        assert_eq!(merged, all);
+
+        let mut merged = small(0..SMALL_SET_LEN);
+        merged.par_union(indexed(SMALL_SET_LEN..3 * SMALL_SET_LEN));
+        assert_eq!(merged, all);
+        assert_eq!(hash_of(&merged), hash_of(&all));
+
+        assert_eq!(
+            &all - &small(SMALL_SET_LEN..3 * SMALL_SET_LEN),
+            small(0..SMALL_SET_LEN)
+        );
+        assert_eq!(&small(0..SMALL_SET_LEN) & &all, small(0..SMALL_SET_LEN));
+    }
+
+    #[test]
+    fn attribute_stats_match_scan() {
+        let knights: Vec<Id> = (0..20).map(|_| ufoid()).collect();
+        let mut set = TribleSet::new();
+        for (i, knight) in knights.iter().enumerate() {
+            set.union(knights::entity!(*knight, {
+                name: (&format!("Knight {}", i % 7)[..]).try_into().unwrap(),
+                loves: knights[(i + 1) % knights.len()]
+            }));
+        }
+        set.union(knights::entity!(ufoid(), { name: "Knight 0".try_into().unwrap() }));
+
+        let mut scanned: BTreeMap<Id, (HashSet<Id>, HashSet<Value>, u64)> = BTreeMap::new();
+        for t in set.iter_ordered() {
+            let e: Id = t[0..ID_LEN].try_into().unwrap();
+            let a: Id = t[ID_LEN..2 * ID_LEN].try_into().unwrap();
+            let v: Value = t[2 * ID_LEN..].try_into().unwrap();
+            let (entities, values, tribles) = scanned.entry(a).or_default();
+            entities.insert(e);
+            values.insert(v);
+            *tribles += 1;
+        }
+
+        let attributes: Vec<(Id, u64)> = set.attributes().collect();
+        assert_eq!(
+            attributes,
+            scanned
+                .iter()
+                .map(|(a, (_, _, tribles))| (*a, *tribles))
+                .collect::<Vec<_>>()
+        );
+        for (a, (entities, values, tribles)) in &scanned {
+            assert_eq!(
+                set.attribute_stats(a),
+                AttributeStats {
+                    entities: entities.len() as u64,
+                    values: values.len() as u64,
+                    tribles: *tribles,
+                }
+            );
+        }
+        assert_eq!(set.attribute_stats(&ufoid()), AttributeStats::default());
+        assert_eq!(TribleSet::new().attributes().count(), 0);
+    }
+
     proptest! {
+        #[test]
+        fn singleton(entry in prop::collection::vec(0u8..255, 64)) {
+            let mut key = [0; 64];
+            key.iter_mut().set_from(entry.iter().cloned());
+            let trible = Trible{ data: key };
+
+            let mut set = TribleSet::new();
+            set.insert(&trible);
+            let single = TribleSet::singleton(&trible);
+
+            prop_assert_eq!(single.len(), 1);
+            prop_assert_eq!(single, set);
+        }
+
+        #[test]
+        fn intersection_and_difference(
+            left in prop::collection::vec((0u8..4, 0u8..4, 0u8..4), 0..64),
+            right in prop::collection::vec((0u8..4, 0u8..4, 0u8..4), 0..64)
+        ) {
+            fn trible((e, a, v): (u8, u8, u8)) -> [u8; 64] {
+                let mut data = [v + 1; 64];
+                data[0..16].fill(e + 1);
+                data[16..32].fill(a + 1);
+                data
+            }
+            fn build(tribles: &HashSet<[u8; 64]>) -> TribleSet {
+                let mut set = TribleSet::new();
+                for data in tribles {
+                    set.insert_raw(data);
+                }
+                set
+            }
+            fn check(set: &TribleSet, model: &HashSet<[u8; 64]>) -> Result<(), TestCaseError> {
+                prop_assert_eq!(set.len(), model.len());
+                let set_indices = set.indices();
+                let indices: [HashSet<[u8; 64]>; 6] = [
+                    set_indices.eav.into_iter().map(|k| EAVOrder::key_ordered(&k)).collect(),
+                    set_indices.eva.into_iter().map(|k| EVAOrder::key_ordered(&k)).collect(),
+                    set_indices.aev.into_iter().map(|k| AEVOrder::key_ordered(&k)).collect(),
+                    set_indices.ave.into_iter().map(|k| AVEOrder::key_ordered(&k)).collect(),
+                    set_indices.vea.into_iter().map(|k| VEAOrder::key_ordered(&k)).collect(),
+                    set_indices.vae.into_iter().map(|k| VAEOrder::key_ordered(&k)).collect(),
+                ];
+                for index in &indices {
+                    prop_assert_eq!(index, model);
+                }
+
+                for i in 0..4u8 {
+                    let probe = trible((i, i, i));
+                    let entity: Id = probe[0..16].try_into().unwrap();
+                    let attribute: Id = probe[16..32].try_into().unwrap();
+                    let value: Value = probe[32..64].try_into().unwrap();
+                    let by_entity: HashSet<(Id, Id, Value)> =
+                        find!(ctx, (e, a, v), and!(e.is(entity), set.pattern(e, a, v)))
+                            .map(|r| r.unwrap())
+                            .collect();
+                    let by_attribute: HashSet<(Id, Id, Value)> =
+                        find!(ctx, (e, a, v), and!(a.is(attribute), set.pattern(e, a, v)))
+                            .map(|r| r.unwrap())
+                            .collect();
+                    let by_value: HashSet<(Id, Id, Value)> =
+                        find!(ctx, (e, a, v), and!(v.is(value), set.pattern(e, a, v)))
+                            .map(|r| r.unwrap())
+                            .collect();
+                    let expected = |f: &dyn Fn(&[u8; 64]) -> bool| -> HashSet<(Id, Id, Value)> {
+                        model
+                            .iter()
+                            .filter(|d| f(d))
+                            .map(|d| {
+                                (
+                                    d[0..16].try_into().unwrap(),
+                                    d[16..32].try_into().unwrap(),
+                                    d[32..64].try_into().unwrap(),
+                                )
+                            })
+                            .collect()
+                    };
+                    prop_assert_eq!(by_entity, expected(&|d| d[0..16] == probe[0..16]));
+                    prop_assert_eq!(by_attribute, expected(&|d| d[16..32] == probe[16..32]));
+                    prop_assert_eq!(by_value, expected(&|d| d[32..64] == probe[32..64]));
+                }
+                Ok(())
+            }
+
+            let left: HashSet<[u8; 64]> = left.into_iter().map(trible).collect();
+            let right: HashSet<[u8; 64]> = right.into_iter().map(trible).collect();
+            let (left_set, right_set) = (build(&left), build(&right));
+
+            let intersection = left_set.intersection(&right_set);
+            check(&intersection, &left.intersection(&right).copied().collect())?;
+            prop_assert_eq!(&left_set & &right_set, intersection);
+
+            let difference = left_set.difference(&right_set);
+            check(&difference, &left.difference(&right).copied().collect())?;
+            prop_assert_eq!(&left_set - &right_set, difference);
+
+            check(&left_set, &left)?;
+            check(&right_set, &right)?;
+        }
+
+        #[test]
+        fn representations_agree(
+            left in prop::collection::vec(0u8..4, 0..2 * SMALL_SET_LEN),
+            right in prop::collection::vec(0u8..4, 0..2 * SMALL_SET_LEN)
+        ) {
+            fn build(entries: &[u8]) -> (TribleSet, TribleSet) {
+                let mut tribles: Vec<[u8; 64]> = entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &v)| {
+                        let mut data = [v; 64];
+                        data[0] = i as u8;
+                        data
+                    })
+                    .collect();
+                let inserted = tribles.iter().map(|&data| Trible { data }).collect();
+                tribles.sort();
+                (inserted, TribleSet::from(TribleIndices::from_tribles(&tribles)))
+            }
+            let (left_small, left_indexed) = build(&left);
+            let (right_small, right_indexed) = build(&right);
+
+            prop_assert_eq!(&left_small, &left_indexed);
+            prop_assert_eq!(hash_of(&left_small), hash_of(&left_indexed));
+            prop_assert_eq!(
+                left_small == right_small,
+                left_indexed == right_indexed
+            );
+            prop_assert_eq!(left_small == right_indexed, left_indexed == right_small);
+            prop_assert_eq!(
+                left_small.iter_ordered().collect::<Vec<_>>(),
+                left_indexed.iter_ordered().collect::<Vec<_>>()
+            );
+
+            let mut mixed = left_small.clone();
+            mixed.union(right_indexed.clone());
+            let mut indexed = left_indexed.clone();
+            indexed.union(right_indexed.clone());
+            let mut small = left_small.clone();
+            small.union(right_small.clone());
+            prop_assert_eq!(&mixed, &indexed);
+            prop_assert_eq!(&small, &indexed);
+            prop_assert_eq!(hash_of(&small), hash_of(&indexed));
+        }
+
         #[test]
         fn insert(entries in prop::collection::vec(prop::collection::vec(0u8..255, 64), 1..1024)) {
             let mut set = TribleSet::new();
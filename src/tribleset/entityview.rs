@@ -0,0 +1,195 @@
+//! Read access to all attributes of a single entity.
+//!
+//! An [EntityView] is a borrowed window into the EAV index of a
+//! [TribleSet], so creating one only checks that the entity exists and
+//! every attribute lookup is a prefix probe into the entity's subtree.
+//!
+//! Values are read through the typed attribute ids of a namespace, so they
+//! are always decoded with the type the namespace declares:
+//!
+//! ```
+//! use std::convert::TryInto;
+//! use tribles::{types::ShortString, ufoid, NS};
+//!
+//! NS! {
+//!     pub namespace knights {
+//!         "A1F4C2B3D5E6F708192A3B4C5D6E7F80" as name: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! let romeo = ufoid();
+//! let set = knights::entity!(romeo, { name: "Romeo".try_into().unwrap() });
+//!
+//! let view = set.entity(romeo).unwrap();
+//! let name = view.get(knights::attributes::name).unwrap().unwrap();
+//! assert_eq!(name, "Romeo".try_into().unwrap());
+//! ```
+//!
+//! Decoding an attribute as another type doesn't compile:
+//!
+//! ```compile_fail
+//! use std::convert::TryInto;
+//! use tribles::{types::ShortString, ufoid, Id, NS};
+//!
+//! NS! {
+//!     pub namespace knights {
+//!         "A1F4C2B3D5E6F708192A3B4C5D6E7F80" as name: tribles::types::ShortString;
+//!     }
+//! }
+//!
+//! let romeo = ufoid();
+//! let set = knights::entity!(romeo, { name: "Romeo".try_into().unwrap() });
+//!
+//! let view = set.entity(romeo).unwrap();
+//! let name: Id = view.get(knights::attributes::name).unwrap().unwrap();
+//! ```
+
+use crate::namespace::Attribute;
+use crate::trible::Trible;
+use crate::{Id, TribleSet, Value, ValueParseError, Valuelike, ID_LEN, VALUE_LEN};
+
+#[derive(Debug, Clone, Copy)]
+pub struct EntityView<'a> {
+    set: &'a TribleSet,
+    id: Id,
+}
+
+impl<'a> EntityView<'a> {
+    pub(super) fn new(set: &'a TribleSet, id: Id) -> Self {
+        EntityView { set, id }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// The attributes of the entity in ascending order.
+    pub fn attributes(&self) -> impl Iterator<Item = Id> {
+        let mut attributes = vec![];
+        self.set
+            .eav_infixes::<ID_LEN, ID_LEN, _>(&self.id, |a| attributes.push(a));
+        attributes.into_iter()
+    }
+
+    /// The raw values of `attribute` in ascending order.
+    pub fn values(&self, attribute: Id) -> Vec<Value> {
+        let mut prefix = [0; ID_LEN * 2];
+        prefix[0..ID_LEN].copy_from_slice(&self.id);
+        prefix[ID_LEN..].copy_from_slice(&attribute);
+
+        let mut values = vec![];
+        self.set
+            .eav_infixes::<{ ID_LEN * 2 }, VALUE_LEN, _>(&prefix, |v| values.push(v));
+        values
+    }
+
+    /// Returns the value of `attribute`, or `None` if the entity doesn't
+    /// have the attribute. For multi-valued attributes the smallest value
+    /// is returned.
+    pub fn get<V>(&self, attribute: Attribute<V>) -> Option<Result<V, ValueParseError>>
+    where
+        V: Valuelike,
+    {
+        self.values(attribute.id())
+            .first()
+            .map(|&v| V::from_value(v))
+    }
+
+    /// Returns all values of `attribute` in ascending order of their
+    /// raw representation.
+    pub fn get_all<V>(
+        &self,
+        attribute: Attribute<V>,
+    ) -> impl Iterator<Item = Result<V, ValueParseError>>
+    where
+        V: Valuelike,
+    {
+        self.values(attribute.id()).into_iter().map(V::from_value)
+    }
+
+    /// Copies the tribles of the entity into a new set.
+    pub fn to_set(&self) -> TribleSet {
+        let mut set = TribleSet::new();
+        for a in self.attributes() {
+            for v in self.values(a) {
+                set.insert(&Trible::new(self.id, a, v));
+            }
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::{types::ShortString, ufoid, NS};
+
+    NS! {
+        pub namespace knights {
+            "6F1E3B2A4D5C0718293A4B5C6D7E8F90" as loves: Id;
+            "7A2F4C3B5E6D1829304B5C6D7E8F9011" as name: ShortString;
+            "8B305D4C6F7E2930415C6D7E8F901122" as title: ShortString;
+        }
+    }
+
+    #[test]
+    fn multi_valued() {
+        let romeo = ufoid();
+        let juliet = ufoid();
+        let mut set = knights::entity!(romeo, {
+            name: "Romeo".try_into().unwrap(),
+            title: "Montague".try_into().unwrap(),
+            title: "Lover".try_into().unwrap(),
+            loves: juliet
+        });
+        set.union(knights::entity!(juliet, {
+            name: "Juliet".try_into().unwrap(),
+            loves: romeo
+        }));
+
+        let view = set.entity(romeo).unwrap();
+        assert_eq!(view.id(), romeo);
+
+        let mut expected = vec![knights::ids::loves, knights::ids::name, knights::ids::title];
+        expected.sort();
+        assert_eq!(view.attributes().collect::<Vec<_>>(), expected);
+
+        let loves = view.get(knights::attributes::loves).unwrap().unwrap();
+        assert_eq!(loves, juliet);
+
+        let titles: Vec<ShortString> = view
+            .get_all(knights::attributes::title)
+            .map(|t| t.unwrap())
+            .collect();
+        let mut expected: Vec<ShortString> =
+            vec!["Montague".try_into().unwrap(), "Lover".try_into().unwrap()];
+        expected.sort_by_key(|t| Valuelike::into_value(t));
+        assert_eq!(titles, expected);
+
+        let copy = view.to_set();
+        assert_eq!(copy.len(), 4);
+        assert_eq!(
+            copy.entity(romeo)
+                .unwrap()
+                .values(knights::ids::title)
+                .len(),
+            2
+        );
+        assert!(copy.entity(juliet).is_none());
+    }
+
+    #[test]
+    fn missing() {
+        let romeo = ufoid();
+        let set = knights::entity!(romeo, { name: "Romeo".try_into().unwrap() });
+
+        assert!(set.entity(ufoid()).is_none());
+        assert!(TribleSet::new().entity(romeo).is_none());
+
+        let view = set.entity(romeo).unwrap();
+        assert!(view.get(knights::attributes::loves).is_none());
+        assert_eq!(view.get_all(knights::attributes::loves).count(), 0);
+    }
+}
@@ -0,0 +1,316 @@
+//! Entities that expire.
+//!
+//! Cache-like datasets can stamp entities with [expiry_ns::expires_at]. An
+//! entity counts as expired from the lower end of that interval on.
+//! [TribleSet::filter_expired] removes all tribles of expired entities, so
+//! that queries over the result don't have to filter them one by one, and
+//! [TribleSet::ignore_expired] excludes them from the patterns of a query
+//! instead. Entities without an expiry never expire.
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+use hifitime::Epoch;
+
+use super::TribleSetConstraint;
+use crate::namespace::NS;
+use crate::query::{
+    Binding, Constraint, ConstraintDescription, TriblePattern, Variable, VariableId, VariableSet,
+};
+use crate::trible::{E_END, E_START, V_END, V_START};
+use crate::types::time::NsTAIInterval;
+use crate::{id_from_value, Id, TribleSet, Value, Valuelike, ID_LEN};
+
+NS! {
+    pub namespace expiry_ns {
+        "27883578BACE2975CC43E00309AD6D7D" as expires_at: NsTAIInterval;
+    }
+}
+
+impl TribleSet {
+    /// Returns the entities that have expired at `now`.
+    ///
+    /// Only walks the tribles of [expiry_ns::expires_at] in the AEV index.
+    pub fn expired_entities(&self, now: Epoch) -> HashSet<Id> {
+        let now = now.to_tai_duration().total_nanoseconds();
+        let expired = self
+            .indices()
+            .attribute_tribles(&expiry_ns::ids::expires_at)
+            .into_iter()
+            .filter_map(|trible| {
+                let value: Value = trible[V_START..=V_END].try_into().unwrap();
+                let NsTAIInterval(lower, _) = NsTAIInterval::from_value(value).ok()?;
+                if lower <= now {
+                    Some(trible[E_START..=E_END].try_into().unwrap())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        expired
+    }
+
+    /// Returns the tribles of all entities that haven't expired at `now`.
+    pub fn filter_expired(&self, now: Epoch) -> TribleSet {
+        let expired = self.expired_entities(now);
+        if expired.is_empty() {
+            return self.clone();
+        }
+
+        // Subtrees without expired entities stay shared with this set.
+        self.retained(|trible| {
+            let entity: Id = trible[0..ID_LEN].try_into().unwrap();
+            !expired.contains(&entity)
+        })
+    }
+
+    /// Returns a view of this set for queries that ignores the entities
+    /// that have expired at `now`.
+    ///
+    /// The expired entities are looked up once, and every pattern over the
+    /// view excludes them from its entity variable, no matter which
+    /// constraint binds it.
+    pub fn ignore_expired(&self, now: Epoch) -> Unexpired<'_> {
+        Unexpired {
+            set: self,
+            expired: self.expired_entities(now),
+        }
+    }
+}
+
+/// A [TribleSet] without its expired entities, see
+/// [TribleSet::ignore_expired].
+#[derive(Debug, Clone)]
+pub struct Unexpired<'a> {
+    set: &'a TribleSet,
+    expired: HashSet<Id>,
+}
+
+impl<'a> Unexpired<'a> {
+    /// The entities that are ignored.
+    pub fn expired(&self) -> &HashSet<Id> {
+        &self.expired
+    }
+}
+
+impl<'s> TriblePattern for Unexpired<'s> {
+    type PatternConstraint<'a, V>
+        = UnexpiredConstraint<'a, V>
+    where
+        V: Valuelike,
+        Self: 'a;
+
+    fn pattern<'a, V>(
+        &'a self,
+        e: Variable<Id>,
+        a: Variable<Id>,
+        v: Variable<V>,
+    ) -> Self::PatternConstraint<'a, V>
+    where
+        V: Valuelike,
+    {
+        UnexpiredConstraint {
+            constraint: self.set.pattern(e, a, v),
+            entity: e.index,
+            expired: &self.expired,
+        }
+    }
+}
+
+/// A pattern over an [Unexpired] set, which drops expired entities from
+/// the proposals and confirmations of its entity variable.
+pub struct UnexpiredConstraint<'a, V>
+where
+    V: Valuelike,
+{
+    constraint: TribleSetConstraint<'a, V>,
+    entity: VariableId,
+    expired: &'a HashSet<Id>,
+}
+
+impl<'a, V> UnexpiredConstraint<'a, V>
+where
+    V: Valuelike,
+{
+    fn exclude(&self, variable: VariableId, values: &mut Vec<Value>) {
+        if variable == self.entity && !self.expired.is_empty() {
+            values.retain(|&v| !self.expired.contains(&id_from_value(v)));
+        }
+    }
+}
+
+impl<'a, V> Constraint<'a> for UnexpiredConstraint<'a, V>
+where
+    V: Valuelike,
+{
+    fn variables(&self) -> VariableSet {
+        self.constraint.variables()
+    }
+
+    fn variable(&self, variable: VariableId) -> bool {
+        self.constraint.variable(variable)
+    }
+
+    fn estimate(&self, variable: VariableId, binding: &Binding) -> usize {
+        self.constraint.estimate(variable, binding)
+    }
+
+    fn propose(&self, variable: VariableId, binding: &Binding) -> Vec<Value> {
+        let mut values = self.constraint.propose(variable, binding);
+        self.exclude(variable, &mut values);
+        values
+    }
+
+    fn confirm(&self, variable: VariableId, binding: &Binding, proposal: &mut Vec<Value>) {
+        self.constraint.confirm(variable, binding, proposal);
+        self.exclude(variable, proposal);
+    }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::with_children(
+            format!("unexpired ({} expired entities)", self.expired.len()),
+            vec![self.constraint.describe()],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use hifitime::Duration;
+
+    use super::*;
+    use crate::types::ShortString;
+    use crate::{and, find, ufoid};
+
+    NS! {
+        pub namespace cache {
+            "A4E1B7D3C2F5968E0D1A2B3C4D5E6F70" as key: ShortString;
+            "B5F2C8E4D3A6079F1E2B3C4D5E6F7081" as next: Id;
+        }
+    }
+
+    fn at(nanoseconds: i128) -> NsTAIInterval {
+        NsTAIInterval(nanoseconds, nanoseconds)
+    }
+
+    fn epoch(nanoseconds: i128) -> Epoch {
+        Epoch::from_tai_duration(Duration::from_total_nanoseconds(nanoseconds))
+    }
+
+    #[test]
+    fn filter_expired() {
+        let (early, boundary, late, forever) = (ufoid(), ufoid(), ufoid(), ufoid());
+        let mut set = TribleSet::new();
+        set.union(cache::entity!(early, { key: "early".try_into().unwrap() }));
+        set.union(expiry_ns::entity!(early, { expires_at: at(1_000) }));
+        set.union(cache::entity!(boundary, { key: "boundary".try_into().unwrap() }));
+        set.union(expiry_ns::entity!(boundary, { expires_at: at(2_000) }));
+        set.union(cache::entity!(late, { key: "late".try_into().unwrap() }));
+        set.union(expiry_ns::entity!(late, { expires_at: at(3_000) }));
+        set.union(cache::entity!(forever, { key: "forever".try_into().unwrap() }));
+
+        let filtered = set.filter_expired(epoch(2_000));
+        assert!(filtered.entity(early).is_none());
+        assert!(filtered.entity(boundary).is_none());
+        assert_eq!(filtered.entity(late).unwrap().to_set().len(), 2);
+        assert_eq!(filtered.entity(forever).unwrap().to_set().len(), 1);
+        assert_eq!(filtered.len(), 3);
+
+        let unchanged = set.filter_expired(epoch(0));
+        assert_eq!(unchanged.len(), set.len());
+    }
+
+    #[test]
+    fn ignore_expired() {
+        let (early, late, forever) = (ufoid(), ufoid(), ufoid());
+        let mut set = TribleSet::new();
+        set.union(cache::entity!(early, { key: "early".try_into().unwrap() }));
+        set.union(expiry_ns::entity!(early, { expires_at: at(1_000) }));
+        set.union(cache::entity!(late, { key: "late".try_into().unwrap() }));
+        set.union(expiry_ns::entity!(late, { expires_at: at(3_000) }));
+        set.union(cache::entity!(forever, { key: "forever".try_into().unwrap() }));
+
+        let now = epoch(2_000);
+        let live = set.ignore_expired(now);
+        assert_eq!(live.expired(), &std::iter::once(early).collect());
+
+        let mut ignored: Vec<Id> = find!(
+            ctx,
+            (entity, key),
+            cache::pattern!(ctx, live, [{entity @ key: key}])
+        )
+        .map(|r: Result<(Id, ShortString), _>| r.unwrap().0)
+        .collect();
+        ignored.sort();
+
+        let filtered = set.filter_expired(now);
+        let mut expected: Vec<Id> = find!(
+            ctx,
+            (entity, key),
+            cache::pattern!(ctx, filtered, [{entity @ key: key}])
+        )
+        .map(|r: Result<(Id, ShortString), _>| r.unwrap().0)
+        .collect();
+        expected.sort();
+
+        assert_eq!(ignored, expected);
+        assert_eq!(ignored.len(), 2);
+        assert!(!ignored.contains(&early));
+
+        // The entity is bound by another constraint first.
+        assert_eq!(
+            find!(
+                ctx,
+                (entity, key),
+                and!(
+                    entity.is(early),
+                    cache::pattern!(ctx, live, [{entity @ key: key}])
+                )
+            )
+            .map(|r: Result<(Id, ShortString), _>| r.unwrap())
+            .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn ignore_expired_in_every_pattern() {
+        let (first, second, third) = (ufoid(), ufoid(), ufoid());
+        let mut set = TribleSet::new();
+        set.union(cache::entity!(first, { key: "first".try_into().unwrap(), next: second }));
+        set.union(cache::entity!(second, { key: "second".try_into().unwrap(), next: third }));
+        set.union(cache::entity!(third, { key: "third".try_into().unwrap() }));
+        set.union(expiry_ns::entity!(third, { expires_at: at(1_000) }));
+
+        let live = set.ignore_expired(epoch(2_000));
+        let rows: Vec<(Id, Id)> = find!(
+            ctx,
+            (a, from, b, to),
+            cache::pattern!(ctx, live, [
+                {a @ key: from, next: b},
+                {b @ key: to}
+            ])
+        )
+        .map(|r: Result<(Id, ShortString, Id, ShortString), _>| {
+            let (a, _, b, _) = r.unwrap();
+            (a, b)
+        })
+        .collect();
+        assert_eq!(rows, vec![(first, second)]);
+
+        // Unexpired data yields the same rows as without the option.
+        let all = find!(
+            ctx,
+            (a, from, b, to),
+            cache::pattern!(ctx, set, [
+                {a @ key: from, next: b},
+                {b @ key: to}
+            ])
+        )
+        .map(|r: Result<(Id, ShortString, Id, ShortString), _>| r.unwrap())
+        .count();
+        assert_eq!(all, 2);
+    }
+}
@@ -17,7 +17,7 @@ where
     variable_e: Variable<Id>,
     variable_a: Variable<Id>,
     variable_v: Variable<V>,
-    set: &'a TribleSet,
+    set: Cow<'a, TribleIndices>,
 }
 
 impl<'a, V> TribleSetConstraint<'a, V>
@@ -28,7 +28,7 @@ where
         variable_e: Variable<Id>,
         variable_a: Variable<Id>,
         variable_v: Variable<V>,
-        set: &'a TribleSet,
+        set: Cow<'a, TribleIndices>,
     ) -> Self {
         TribleSetConstraint {
             variable_e,
@@ -297,4 +297,14 @@ where
             _ => panic!("invalid trible constraint state"),
         }
     }
+
+    fn describe(&self) -> ConstraintDescription {
+        ConstraintDescription::new(format!(
+            "tribleset {} {} {} ({} tribles)",
+            self.variable_e,
+            self.variable_a,
+            self.variable_v,
+            self.set.len()
+        ))
+    }
 }
@@ -1,6 +1,7 @@
 //! This is a collection of Rust types that can be (de)serialized as
 //! [Value]s, and [Blob]s.
 
+#[cfg(feature = "signatures")]
 pub mod ed25519;
 pub mod f256;
 pub mod hash;
@@ -9,4 +9,8 @@ impl Valuelike for f256 {
     fn into_value(n: &Self) -> crate::Value {
         n.to_be_bytes()
     }
+
+    fn format_value(value: crate::Value) -> Option<String> {
+        Some(f256::from_be_bytes(value).to_string())
+    }
 }
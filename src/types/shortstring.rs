@@ -1,14 +1,27 @@
 use std::convert::TryFrom;
+use std::fmt;
 
 use crate::{Value, ValueParseError, Valuelike};
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum FromStrError {
     TooLong,
     InteriorNul,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+impl fmt::Display for FromStrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLong => write!(f, "string is longer than 32 bytes"),
+            Self::InteriorNul => write!(f, "string contains a nul byte"),
+        }
+    }
+}
+
+impl std::error::Error for FromStrError {}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct ShortString(Value);
 
@@ -40,6 +53,12 @@ impl Valuelike for ShortString {
     fn into_value(shortstring: &Self) -> Value {
         shortstring.0
     }
+
+    fn format_value(value: Value) -> Option<String> {
+        let s = ShortString::from_value(value).ok()?;
+        let s: &str = (&s).into();
+        Some(format!("{:?}", s))
+    }
 }
 
 impl From<&ShortString> for String {
@@ -69,3 +88,32 @@ impl TryFrom<&str> for ShortString {
         ShortString::new(s)
     }
 }
+
+impl fmt::Debug for ShortString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s: &str = self.into();
+        f.debug_tuple("ShortString").field(&s).finish()
+    }
+}
+
+impl fmt::Display for ShortString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s: &str = self.into();
+        f.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting() {
+        let s = ShortString::new("Romeo").unwrap();
+        assert_eq!(format!("{:?}", s), "ShortString(\"Romeo\")");
+        assert_eq!(format!("{}", s), "Romeo");
+
+        let full = ShortString::new("0123456789abcdef0123456789abcdef").unwrap();
+        assert_eq!(format!("{}", full), "0123456789abcdef0123456789abcdef");
+    }
+}
@@ -1,11 +1,48 @@
 use std::convert::TryInto;
+use std::fmt;
 
 use crate::Valuelike;
 
 use hifitime::prelude::*;
 
+/// An interval of TAI time in nanoseconds since the hifitime epoch, with
+/// both ends included.
+///
+/// It is displayed as an RFC 3339 UTC timestamp, or as two timestamps
+/// separated by a slash if the ends differ.
 pub struct NsTAIInterval(pub i128, pub i128);
 
+fn write_rfc3339(f: &mut fmt::Formatter<'_>, nanoseconds: i128) -> fmt::Result {
+    let epoch = Epoch::from_tai_duration(Duration::from_total_nanoseconds(nanoseconds));
+    let (year, month, day, hour, minute, second, nanos) = epoch.to_gregorian_utc();
+    write!(
+        f,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, hour, minute, second
+    )?;
+    if nanos != 0 {
+        write!(f, ".{:09}", nanos)?;
+    }
+    f.write_str("Z")
+}
+
+impl fmt::Display for NsTAIInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_rfc3339(f, self.0)?;
+        if self.1 != self.0 {
+            f.write_str("/")?;
+            write_rfc3339(f, self.1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for NsTAIInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NsTAIInterval({})", self)
+    }
+}
+
 impl Valuelike for NsTAIInterval {
     fn from_value(bytes: crate::Value) -> Result<Self, crate::ValueParseError> {
         let lower = i128::from_be_bytes(bytes[0..16].try_into().unwrap());
@@ -19,6 +56,10 @@ impl Valuelike for NsTAIInterval {
         value[16..32].copy_from_slice(&interval.1.to_be_bytes());
         value
     }
+
+    fn format_value(value: crate::Value) -> Option<String> {
+        Some(NsTAIInterval::from_value(value).ok()?.to_string())
+    }
 }
 
 impl From<(Epoch, Epoch)> for NsTAIInterval {
@@ -50,6 +91,29 @@ mod tests {
         let _ = NsTAIInterval::from_value(value);
     }
 
+    #[test]
+    fn rfc3339() {
+        let noon = Epoch::from_gregorian_utc(2024, 3, 1, 12, 30, 0, 0);
+        let later = Epoch::from_gregorian_utc(2024, 3, 1, 12, 30, 0, 5);
+
+        let instant: NsTAIInterval = (noon, noon).into();
+        assert_eq!(instant.to_string(), "2024-03-01T12:30:00Z");
+        assert_eq!(
+            format!("{:?}", instant),
+            "NsTAIInterval(2024-03-01T12:30:00Z)"
+        );
+
+        let interval: NsTAIInterval = (noon, later).into();
+        assert_eq!(
+            interval.to_string(),
+            "2024-03-01T12:30:00Z/2024-03-01T12:30:00.000000005Z"
+        );
+        assert_eq!(
+            NsTAIInterval::format_value(NsTAIInterval::into_value(&interval)),
+            Some(interval.to_string())
+        );
+    }
+
     #[test]
     fn hifitime_conversion() {
         let epoch: NsTAIInterval = NsTAIInterval(0, 0);
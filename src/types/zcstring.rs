@@ -1,5 +1,8 @@
+use std::fmt;
+
 use anybytes::Bytes;
 use digest::{Digest, typenum::U32};
+use crate::debug::{write_truncated, STRING_LIMIT};
 use crate::{BlobParseError, Bloblike, Handle};
 
 use super::Hash;
@@ -15,6 +18,14 @@ impl std::ops::Deref for ZCString {
     }
 }
 
+impl fmt::Debug for ZCString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ZCString(")?;
+        write_truncated(f, self, STRING_LIMIT)?;
+        f.write_str(")")
+    }
+}
+
 impl From<String> for ZCString {
     fn from(value: String) -> Self {
         ZCString(value.into())
@@ -53,4 +64,22 @@ mod tests {
 
         assert!(h == h2);
     }
+
+    #[test]
+    fn truncated_debug() {
+        let short: ZCString = String::from("hello world!").into();
+        assert_eq!(format!("{:?}", short), "ZCString(\"hello world!\")");
+
+        let long: ZCString = "Fear is the mind-killer. ".repeat(10).into();
+        assert_eq!(
+            format!("{:?}", long),
+            "ZCString(\"Fear is the mind-killer. Fear is the mind-killer. Fear is the mi\"\u{2026} (250 bytes))"
+        );
+
+        let wide: ZCString = "\u{e4}".repeat(40).into();
+        assert_eq!(
+            format!("{:?}", wide),
+            format!("ZCString(\"{}\"\u{2026} (80 bytes))", "\u{e4}".repeat(32))
+        );
+    }
 }
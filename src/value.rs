@@ -1,4 +1,5 @@
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{self, Debug};
 
 pub const VALUE_LEN: usize = 32;
 pub type Value = [u8; VALUE_LEN];
@@ -7,6 +8,12 @@ pub type Value = [u8; VALUE_LEN];
 pub trait Valuelike: Sized {
     fn from_value(bytes: Value) -> Result<Self, ValueParseError>;
     fn into_value(item: &Self) -> Value;
+
+    /// A short human readable rendering of `value` for debug output, see
+    /// [crate::debug], or `None` to show the raw bytes.
+    fn format_value(_value: Value) -> Option<String> {
+        None
+    }
 }
 
 impl Valuelike for Value {
@@ -19,6 +26,7 @@ impl Valuelike for Value {
     }
 }
 
+#[derive(Clone)]
 pub struct ValueParseError {
     value: Value,
     msg: String,
@@ -47,3 +55,16 @@ impl Debug for ValueParseError {
             .finish()
     }
 }
+
+impl fmt::Display for ValueParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to parse value {}: {}",
+            hex::encode(&self.value),
+            self.msg
+        )
+    }
+}
+
+impl Error for ValueParseError {}
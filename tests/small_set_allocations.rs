@@ -0,0 +1,77 @@
+//! Counts the allocations of building `entity!` fragments, which are kept
+//! as small sets, against inserting the same tribles into indexed sets.
+//!
+//! Lives in its own test binary, so that the counting allocator only sees
+//! the fragments and not other tests running in parallel.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tribles::tribleset::{TribleIndices, SMALL_SET_LEN};
+use tribles::{types::ShortString, Id, TribleSet, NS};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+NS! {
+    pub namespace knights {
+        "39E2D06DBCD9CB96DE5BC46F362CFF31" as loves: Id;
+        "7D4F339CC4AE0BBA2765F34BE1D108EF" as name: ShortString;
+    }
+}
+
+const FRAGMENTS: usize = 10_000;
+
+/// Counts the allocations of `f`, which builds one set per fragment.
+fn allocations(ids: &[Id], f: impl Fn(Id, Id) -> TribleSet) -> usize {
+    let mut sets = Vec::with_capacity(FRAGMENTS);
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for pair in ids.windows(2) {
+        sets.push(f(pair[0], pair[1]));
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn entity_fragments_allocate_once() {
+    assert!(2 <= SMALL_SET_LEN);
+    let ids: Vec<Id> = (0..=FRAGMENTS as u128).map(|i| i.to_be_bytes()).collect();
+    let name: ShortString = "Lancelot".try_into().unwrap();
+
+    let small = allocations(
+        &ids,
+        |lover_a, lover_b| knights::entity!(lover_a, { name: name.clone(), loves: lover_b }),
+    );
+    let indexed = allocations(&ids, |lover_a, lover_b| {
+        let mut set = TribleSet::from(TribleIndices::EMPTY);
+        knights::entity!(&mut set, lover_a, { name: name.clone(), loves: lover_b });
+        set
+    });
+
+    println!(
+        "{} fragments: {} allocations as small sets, {} indexed",
+        FRAGMENTS, small, indexed
+    );
+    assert!(small <= FRAGMENTS, "small sets allocated {} times", small);
+    assert!(
+        indexed >= 4 * small,
+        "indexed sets allocated {} times",
+        indexed
+    );
+}
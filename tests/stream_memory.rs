@@ -0,0 +1,77 @@
+//! Checks that streaming merges don't grow with the size of their inputs.
+//!
+//! Lives in its own test binary, so that the counting allocator only sees
+//! the merge and not other tests running in parallel.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tribles::trible::stream::write_union;
+use tribles::trible::TRIBLE_LEN;
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Counts the written bytes without keeping them.
+struct CountingSink(usize);
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An EAV ordered stream of `len` tribles, interleaved with the other
+/// streams and generated on the fly.
+fn stream(i: u64, streams: u64, len: u64) -> impl Iterator<Item = [u8; TRIBLE_LEN]> {
+    (0..len).map(move |n| {
+        let mut data = [0; TRIBLE_LEN];
+        data[8..16].copy_from_slice(&(n * streams + i + 1).to_be_bytes());
+        data[31] = 1;
+        data
+    })
+}
+
+#[test]
+fn merge_ten_million_in_constant_memory() {
+    const STREAMS: u64 = 10;
+    const LEN: u64 = 1_000_000;
+
+    let inputs: Vec<_> = (0..STREAMS).map(|i| stream(i, STREAMS, LEN)).collect();
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+
+    let mut sink = CountingSink(0);
+    write_union(inputs, &mut sink).unwrap();
+
+    assert_eq!(sink.0, (STREAMS * LEN) as usize * TRIBLE_LEN);
+    let grown = PEAK.load(Ordering::Relaxed) - baseline;
+    assert!(grown < 64 * 1024, "merge allocated {} bytes", grown);
+}